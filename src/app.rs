@@ -1,14 +1,38 @@
 extern crate sdl2;
 
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::PixelFormatEnum::RGB24;
 use sdl2::surface::Surface;
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, Sender};
 
-pub fn run(channel: Receiver<Vec<u8>>) -> Result<(), String> {
+//how much audio (in frames) we let pile up in the queue. the emulator isnt throttled to the
+//sound card, so past MAX we start dropping chunks to keep the latency down, and after the
+//queue runs dry we wait for PREFILL before playing again instead of crackling through
+//one chunk at a time
+const AUDIO_MAX_FRAMES: usize = 6;
+const AUDIO_PREFILL_FRAMES: usize = 2;
+
+//quit tells the emulator thread the window is going away
+pub fn run(
+    channel: Receiver<Vec<u8>>,
+    audio: Receiver<Vec<f32>>,
+    sample_rate: u32,
+    quit: Sender<()>,
+) -> Result<(), String> {
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
+    let audio_subsystem = sdl_context.audio()?;
+
+    //mono f32, sdl converts to whatever the device actually wants
+    let desired = AudioSpecDesired {
+        freq: Some(sample_rate as i32),
+        channels: Some(1),
+        samples: Some(1024),
+    };
+    let queue: AudioQueue<f32> = audio_subsystem.open_queue(None, &desired)?;
+    let frame_samples = sample_rate as usize / 60;
 
     let window = video_subsystem
         .window("rust-nes", 256, 240)
@@ -39,11 +63,19 @@ pub fn run(channel: Receiver<Vec<u8>>) -> Result<(), String> {
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => break 'running,
+                } => {
+                    //the runner might already be gone, nothing to do about that
+                    let _ = quit.send(());
+                    break 'running;
+                }
                 _ => {}
             }
         }
 
+        while let Ok(chunk) = audio.try_recv() {
+            queue_audio(&queue, &chunk, frame_samples)?;
+        }
+
         match channel.try_recv() {
             Ok(mut frame) => {
                 //256x240 24 bits per pixel
@@ -64,3 +96,20 @@ pub fn run(channel: Receiver<Vec<u8>>) -> Result<(), String> {
 
     Ok(())
 }
+
+//push a chunk of samples onto the queue, keeping its fill level between the prefill and max marks
+fn queue_audio(queue: &AudioQueue<f32>, chunk: &[f32], frame_samples: usize) -> Result<(), String> {
+    let queued = queue.size() as usize / std::mem::size_of::<f32>();
+    if queued > frame_samples * AUDIO_MAX_FRAMES {
+        return Ok(());
+    }
+    //ran dry, hold off until theres a cushion again
+    if queued == 0 {
+        queue.pause();
+    }
+    queue.queue_audio(chunk)?;
+    if queue.size() as usize / std::mem::size_of::<f32>() >= frame_samples * AUDIO_PREFILL_FRAMES {
+        queue.resume();
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//timer periods in cpu cycles, picked by the low 4 bits of $4010
+const NTSC_RATES: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_RATES: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/*delta modulation channel registers
+$4010  IL-- RRRR   irq enable, loop, rate index
+$4011  -DDD DDDD   direct load of the output level
+$4012  AAAA AAAA   sample address, $C000 + A * 64
+$4013  LLLL LLLL   sample length, L * 16 + 1 bytes
+
+the dmc plays 1 bit deltas out of a sample in prg rom. it cant get at the bus itself,
+so whenever its one byte buffer runs dry it asks the cpu to stop and fetch the next
+byte for it (see NES::dmc_dma)*/
+#[derive(Clone)]
+pub struct Dmc {
+    pub pal: bool,
+    pub irq_enabled: bool,
+    pub looping: bool,
+    pub timer_period: u16,
+    pub timer: u16,
+    //7 bit dac level, moved up or down by 2 for every bit played
+    pub output_level: u8,
+
+    //where the sample starts and how long it is, from $4012/$4013
+    pub sample_addr: u16,
+    pub sample_length: u16,
+    //memory reader. where the next byte comes from and how many are left
+    pub current_addr: u16,
+    pub bytes_remaining: u16,
+    //the byte the reader fetched, waiting for the output unit to need it
+    pub sample_buffer: Option<u8>,
+
+    //output unit. shifts 8 bits out of its own copy of the buffer
+    pub shift: u8,
+    pub bits_remaining: u8,
+    //nothing was in the buffer when the last byte ran out, so the level holds still
+    pub silence: bool,
+
+    //raised when a non looping sample finishes with irq enabled
+    pub irq: bool,
+}
+
+impl Dmc {
+    pub fn new(pal: bool) -> Self {
+        Dmc {
+            pal,
+            irq_enabled: false,
+            looping: false,
+            timer_period: if pal { PAL_RATES[0] } else { NTSC_RATES[0] },
+            timer: 0,
+            output_level: 0,
+            sample_addr: 0xC000,
+            sample_length: 1,
+            current_addr: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift: 0,
+            bits_remaining: 8,
+            silence: true,
+            irq: false,
+        }
+    }
+
+    //reg is 0-3, the offset from $4010
+    pub fn write(&mut self, reg: u16, byte: u8) {
+        match reg {
+            0 => {
+                self.irq_enabled = (byte & 0b1000_0000) != 0;
+                self.looping = (byte & 0b0100_0000) != 0;
+                let table = if self.pal { PAL_RATES } else { NTSC_RATES };
+                self.timer_period = table[(byte & 0x0F) as usize];
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+            }
+            1 => self.output_level = byte & 0b0111_1111,
+            2 => self.sample_addr = 0xC000 | (byte as u16) << 6,
+            3 => self.sample_length = (byte as u16) << 4 | 1,
+            _ => unreachable!("the dmc only has 4 registers"),
+        }
+    }
+
+    //the $4015 enable bit. turning it off drops the rest of the sample, turning it on
+    //only restarts the sample if the last one already finished
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_addr = self.sample_addr;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    //the address the reader wants a byte from, if its buffer is empty and theres sample left
+    pub fn dma_request(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_addr)
+        } else {
+            None
+        }
+    }
+
+    //the cpu finished the fetch dma_request asked for
+    pub fn fill_buffer(&mut self, byte: u8) {
+        self.sample_buffer = Some(byte);
+        //wraps around to $8000, not $0000
+        self.current_addr = self.current_addr.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    //once per cpu cycle, the table is already in cpu cycles
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period - 1;
+
+        if !self.silence {
+            //the level clamps instead of wrapping
+            if (self.shift & 1) != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(byte) => {
+                    self.silence = false;
+                    self.shift = byte;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    //0-127
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+}
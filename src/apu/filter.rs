@@ -0,0 +1,44 @@
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy)]
+pub enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+//first order rc filter. the nes (and the tv it's plugged into) runs the mixed signal through
+//two high passes at 90hz and 440hz and a low pass at 14khz before it reaches the speaker
+#[derive(Clone)]
+pub struct Filter {
+    kind: FilterKind,
+    alpha: f32,
+    prev_in: f32,
+    prev_out: f32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, cutoff: f32, sample_rate: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+        let alpha = match kind {
+            FilterKind::HighPass => rc / (rc + dt),
+            FilterKind::LowPass => dt / (rc + dt),
+        };
+        Filter {
+            kind,
+            alpha,
+            prev_in: 0.0,
+            prev_out: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let out = match self.kind {
+            FilterKind::HighPass => self.alpha * (self.prev_out + sample - self.prev_in),
+            FilterKind::LowPass => self.prev_out + self.alpha * (sample - self.prev_out),
+        };
+        self.prev_in = sample;
+        self.prev_out = out;
+        out
+    }
+}
@@ -0,0 +1,113 @@
+//cpu cycles (from the start of the sequence) where the 5 steps land
+const NTSC_STEPS: [u32; 5] = [7457, 14913, 22371, 29829, 37281];
+const PAL_STEPS: [u32; 5] = [8313, 16627, 24939, 33253, 41565];
+
+//what the rest of the apu should clock this cycle
+#[derive(Clone, Copy, Default)]
+pub struct FrameClocks {
+    //envelopes and the triangle's linear counter
+    pub quarter: bool,
+    //length counters and sweeps
+    pub half: bool,
+}
+
+/*$4017  MI-- ----   sequencer mode (0 = 4 step, 1 = 5 step), irq inhibit
+
+4 step mode          5 step mode
+step  clocks         step  clocks
+1     quarter        1     quarter
+2     quarter, half  2     quarter, half
+3     quarter        3     quarter
+4     quarter, half, 4     (nothing)
+      irq            5     quarter, half*/
+#[derive(Clone)]
+pub struct FrameCounter {
+    pub pal: bool,
+    pub five_step: bool,
+    pub irq_inhibit: bool,
+    //the frame irq flag, raised at the end of every 4 step sequence. it stays up (and keeps
+    //the irq line asserted) until $4015 is read or irq inhibit gets set
+    pub irq: bool,
+    pub cycle: u32,
+    //a $4017 write restarts the sequence 3 or 4 cpu cycles later, depending on whether
+    //it landed on an apu cycle or between two of them
+    pub restart_delay: Option<u8>,
+}
+
+impl FrameCounter {
+    pub fn new(pal: bool) -> Self {
+        FrameCounter {
+            pal,
+            five_step: false,
+            irq_inhibit: false,
+            irq: false,
+            cycle: 0,
+            restart_delay: None,
+        }
+    }
+
+    //odd_cycle is whether the write happened between apu cycles
+    pub fn write(&mut self, byte: u8, odd_cycle: bool) {
+        self.five_step = (byte & 0b1000_0000) != 0;
+        self.irq_inhibit = (byte & 0b0100_0000) != 0;
+        if self.irq_inhibit {
+            self.irq = false;
+        }
+        self.restart_delay = Some(if odd_cycle { 4 } else { 3 });
+    }
+
+    //one cpu cycle
+    pub fn tick(&mut self) -> FrameClocks {
+        let mut clocks = FrameClocks::default();
+
+        if let Some(delay) = self.restart_delay {
+            if delay <= 1 {
+                self.restart_delay = None;
+                self.cycle = 0;
+                //switching to 5 step mode clocks everything right away
+                if self.five_step {
+                    clocks.quarter = true;
+                    clocks.half = true;
+                }
+                return clocks;
+            }
+            self.restart_delay = Some(delay - 1);
+        }
+
+        let steps = if self.pal { PAL_STEPS } else { NTSC_STEPS };
+        self.cycle += 1;
+
+        if self.cycle == steps[0] || self.cycle == steps[2] {
+            clocks.quarter = true;
+        } else if self.cycle == steps[1] {
+            clocks.quarter = true;
+            clocks.half = true;
+        } else if !self.five_step {
+            //the irq flag gets set on the 3 cycles around the last step, and the last one
+            //is also cycle 0 of the next sequence
+            if self.cycle == steps[3] - 1 {
+                self.raise_irq();
+            } else if self.cycle == steps[3] {
+                clocks.quarter = true;
+                clocks.half = true;
+                self.raise_irq();
+            } else if self.cycle == steps[3] + 1 {
+                self.raise_irq();
+                self.cycle = 0;
+            }
+        } else if self.cycle == steps[4] {
+            clocks.quarter = true;
+            clocks.half = true;
+        } else if self.cycle == steps[4] + 1 {
+            self.cycle = 0;
+        }
+
+        clocks
+    }
+
+    fn raise_irq(&mut self) {
+        if !self.irq_inhibit {
+            self.irq = true;
+        }
+    }
+}
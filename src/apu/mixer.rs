@@ -0,0 +1,39 @@
+//the channels dont get added together linearly on the real chip. the pulses share one dac and
+//triangle/noise/dmc share another, and both saturate as more current flows through them.
+//https://www.nesdev.org/wiki/APU_Mixer has the formulas, we precompute them for every input
+#[derive(Clone)]
+pub struct Mixer {
+    //indexed by pulse1 + pulse2 (0-30)
+    pulse_table: Vec<f32>,
+    //indexed by 3 * triangle + 2 * noise + dmc (0-202)
+    tnd_table: Vec<f32>,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        let pulse_table = (0..31)
+            .map(|n| match n {
+                0 => 0.0,
+                _ => 95.52 / (8128.0 / n as f32 + 100.0),
+            })
+            .collect();
+        let tnd_table = (0..203)
+            .map(|n| match n {
+                0 => 0.0,
+                _ => 163.67 / (24329.0 / n as f32 + 100.0),
+            })
+            .collect();
+
+        Mixer {
+            pulse_table,
+            tnd_table,
+        }
+    }
+
+    //channel outputs in, 0.0-1.0 out
+    pub fn mix(&self, pulse1: u8, pulse2: u8, triangle: u8, noise: u8, dmc: u8) -> f32 {
+        let pulse = self.pulse_table[(pulse1 + pulse2) as usize];
+        let tnd = self.tnd_table[3 * triangle as usize + 2 * noise as usize + dmc as usize];
+        pulse + tnd
+    }
+}
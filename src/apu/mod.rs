@@ -0,0 +1,189 @@
+mod dmc;
+mod filter;
+mod frame_counter;
+mod mixer;
+mod noise;
+mod output;
+mod pulse;
+mod resampler;
+mod triangle;
+mod units;
+
+pub use dmc::Dmc;
+pub use frame_counter::FrameCounter;
+pub use mixer::Mixer;
+pub use noise::Noise;
+pub use output::AudioOutput;
+pub use pulse::Pulse;
+pub use triangle::Triangle;
+
+//names for each channel, in the order channel_outputs keeps them
+pub const CHANNEL_NAMES: [&str; 5] = ["pulse1", "pulse2", "triangle", "noise", "dmc"];
+
+/*APU registers, mapped into cpu space at $4000-$4017
+$4000-$4003   pulse 1
+$4004-$4007   pulse 2
+$4008-$400B   triangle
+$400C-$400F   noise
+$4010-$4013   dmc
+$4015         channel enables (write) / length counter and irq status (read)
+$4017         frame counter*/
+#[derive(Clone)]
+pub struct Apu {
+    pub pulse1: Pulse,
+    pub pulse2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: Dmc,
+    pub frame_counter: FrameCounter,
+    pub mixer: Mixer,
+    //resampling and filtering down to something a sound card can play. None means nobody
+    //is listening, so we dont waste time mixing
+    pub output: Option<AudioOutput>,
+    //same thing for every channel on its own (see CHANNEL_NAMES), for dumping them separately
+    pub channel_outputs: Option<Vec<AudioOutput>>,
+    //cpu cycles since power on. the pulse timers only tick on every other one (an apu cycle)
+    pub cycles: u64,
+}
+
+impl Apu {
+    pub fn new(pal: bool) -> Self {
+        Apu {
+            //pulse 1 gets the ones' complement sweep quirk
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(pal),
+            dmc: Dmc::new(pal),
+            frame_counter: FrameCounter::new(pal),
+            mixer: Mixer::new(),
+            output: None,
+            channel_outputs: None,
+            cycles: 0,
+        }
+    }
+
+    //the reset button silences everything, same as writing 0 to $4015. the frame counter
+    //keeps its mode and restarts as if $4017 got written again
+    pub fn reset(&mut self) {
+        self.write_reg(0x4015, 0);
+        let mode = (self.frame_counter.five_step as u8) << 7;
+        self.write_reg(0x4017, mode);
+    }
+
+    pub fn write_reg(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write(addr - 0x4000, byte),
+            0x4004..=0x4007 => self.pulse2.write(addr - 0x4004, byte),
+            0x4008..=0x400B => self.triangle.write(addr - 0x4008, byte),
+            0x400C..=0x400F => self.noise.write(addr - 0x400C, byte),
+            0x4010..=0x4013 => self.dmc.write(addr - 0x4010, byte),
+            //---D NT21
+            0x4015 => {
+                self.pulse1.length.set_enabled((byte & 0b0000_0001) != 0);
+                self.pulse2.length.set_enabled((byte & 0b0000_0010) != 0);
+                self.triangle.length.set_enabled((byte & 0b0000_0100) != 0);
+                self.noise.length.set_enabled((byte & 0b0000_1000) != 0);
+                self.dmc.set_enabled((byte & 0b0001_0000) != 0);
+            }
+            0x4017 => self
+                .frame_counter
+                .write(byte, !self.cycles.is_multiple_of(2)),
+            _ => {}
+        }
+    }
+
+    //$4015 reads clear the frame irq flag
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_counter.irq = false;
+        status
+    }
+
+    //$4015 without the side effects, for the debugger and logging
+    //IF-D NT21   dmc irq, frame irq, a bit for every channel with a nonzero length counter
+    //(or sample bytes left, for the dmc)
+    pub fn peek_status(&self) -> u8 {
+        (self.pulse1.length.active() as u8)
+            | (self.pulse2.length.active() as u8) << 1
+            | (self.triangle.length.active() as u8) << 2
+            | (self.noise.length.active() as u8) << 3
+            | ((self.dmc.bytes_remaining > 0) as u8) << 4
+            | (self.frame_counter.irq as u8) << 6
+            | (self.dmc.irq as u8) << 7
+    }
+
+    //the apu has two separate sources on the cpu's irq line
+    pub fn frame_irq(&self) -> bool {
+        self.frame_counter.irq
+    }
+    pub fn dmc_irq(&self) -> bool {
+        self.dmc.irq
+    }
+
+    //one cpu cycle
+    pub fn tick(&mut self) {
+        self.cycles += 1;
+        if self.cycles.is_multiple_of(2) {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+
+        let clocks = self.frame_counter.tick();
+        if clocks.quarter {
+            self.quarter_frame();
+        }
+        if clocks.half {
+            self.half_frame();
+        }
+
+        if self.output.is_some() || self.channel_outputs.is_some() {
+            self.clock_outputs();
+        }
+    }
+
+    fn clock_outputs(&mut self) {
+        let levels = [
+            self.pulse1.output(),
+            self.pulse2.output(),
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        ];
+
+        if let Some(output) = &mut self.output {
+            let [p1, p2, t, n, d] = levels;
+            output.clock(self.mixer.mix(p1, p2, t, n, d));
+        }
+        //each channel goes through the mixer alone, so they come out at the same scale as the mix
+        if let Some(outputs) = &mut self.channel_outputs {
+            for (i, output) in outputs.iter_mut().enumerate() {
+                let mut solo = [0; 5];
+                solo[i] = levels[i];
+                let [p1, p2, t, n, d] = solo;
+                output.clock(self.mixer.mix(p1, p2, t, n, d));
+            }
+        }
+    }
+
+    //envelopes and the triangle's linear counter
+    fn quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear();
+    }
+
+    //length counters and sweeps
+    fn half_frame(&mut self) {
+        self.pulse1.length.clock();
+        self.pulse2.length.clock();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+}
@@ -0,0 +1,83 @@
+use super::units::{Envelope, LengthCounter};
+
+//timer periods in cpu cycles, picked by the low 4 bits of $400E
+const NTSC_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+/*noise channel registers
+$400C  --LC VVVV   length counter halt / envelope loop, constant volume, volume/envelope period
+$400D  ---- ----   unused
+$400E  M--- PPPP   mode, period index
+$400F  LLLL L---   length counter load*/
+#[derive(Clone)]
+pub struct Noise {
+    pub pal: bool,
+    //mode 1 taps bit 6 instead of bit 1, which makes a short 93 (or 31) step metallic loop
+    pub mode: bool,
+    pub timer_period: u16,
+    pub timer: u16,
+    //15 bit linear feedback shift register, starts at 1 on power up
+    pub shift: u16,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+}
+
+impl Noise {
+    pub fn new(pal: bool) -> Self {
+        Noise {
+            pal,
+            mode: false,
+            timer_period: NTSC_PERIODS[0],
+            timer: 0,
+            shift: 1,
+            length: LengthCounter::default(),
+            envelope: Envelope::default(),
+        }
+    }
+
+    //reg is 0-3, the offset from $400C
+    pub fn write(&mut self, reg: u16, byte: u8) {
+        match reg {
+            0 => {
+                self.length.halt = (byte & 0b0010_0000) != 0;
+                self.envelope.write(byte);
+            }
+            1 => {}
+            2 => {
+                self.mode = (byte & 0b1000_0000) != 0;
+                let table = if self.pal { PAL_PERIODS } else { NTSC_PERIODS };
+                self.timer_period = table[(byte & 0x0F) as usize];
+            }
+            3 => {
+                self.length.load(byte >> 3);
+                self.envelope.start = true;
+            }
+            _ => unreachable!("the noise channel only has 4 registers"),
+        }
+    }
+
+    //once per cpu cycle, the table is already in cpu cycles
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let tap = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift ^ (self.shift >> tap)) & 1;
+            self.shift = (self.shift >> 1) | feedback << 14;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    //0-15
+    pub fn output(&self) -> u8 {
+        if !self.length.active() || (self.shift & 1) != 0 {
+            0
+        } else {
+            self.envelope.volume()
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use super::filter::{Filter, FilterKind};
+use super::resampler::Resampler;
+use std::sync::mpsc::Sender;
+
+//cpu clock rates, the apu gets clocked at the same speed
+pub const NTSC_CLOCK_RATE: f64 = 1_789_773.0;
+pub const PAL_CLOCK_RATE: f64 = 1_662_607.0;
+
+//everything between the mixer and the speakers: resample down to the output rate,
+//run it through the console's filters, and ship it off a frame's worth at a time
+#[derive(Clone)]
+pub struct AudioOutput {
+    resampler: Resampler,
+    filters: Vec<Filter>,
+    //finished samples that havent been sent yet
+    pub samples: Vec<f32>,
+    //where full chunks go. with no sender the samples just pile up until someone takes them
+    sender: Option<Sender<Vec<f32>>>,
+    //about one frame of audio
+    chunk_size: usize,
+}
+
+impl AudioOutput {
+    pub fn new(pal: bool, sample_rate: u32, sender: Option<Sender<Vec<f32>>>) -> Self {
+        let clock_rate = if pal { PAL_CLOCK_RATE } else { NTSC_CLOCK_RATE };
+        let rate = sample_rate as f32;
+        AudioOutput {
+            resampler: Resampler::new(clock_rate, sample_rate as f64),
+            filters: vec![
+                Filter::new(FilterKind::HighPass, 90.0, rate),
+                Filter::new(FilterKind::HighPass, 440.0, rate),
+                Filter::new(FilterKind::LowPass, 14000.0, rate),
+            ],
+            samples: Vec::new(),
+            sender,
+            chunk_size: sample_rate as usize / 60,
+        }
+    }
+
+    //one cpu cycle's worth of mixer output
+    pub fn clock(&mut self, level: f32) {
+        self.resampler.clock(level);
+        //dont bother integrating every single cycle
+        if self.resampler.available() < 64 {
+            return;
+        }
+
+        self.read_resampler();
+
+        if self.samples.len() >= self.chunk_size {
+            if let Some(sender) = &self.sender {
+                //the app might have already closed, in which case nobody is listening anyway
+                let _ = sender.send(std::mem::take(&mut self.samples));
+            }
+        }
+    }
+
+    //everything that hasnt been sent yet, including whatever was still in the resampler
+    pub fn finish(&mut self) -> Vec<f32> {
+        self.read_resampler();
+        std::mem::take(&mut self.samples)
+    }
+
+    fn read_resampler(&mut self) {
+        let start = self.samples.len();
+        self.resampler.read(&mut self.samples);
+        for sample in &mut self.samples[start..] {
+            for filter in &mut self.filters {
+                *sample = filter.process(*sample);
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use super::units::{Envelope, LengthCounter};
+
+//the 4 duty cycles, 12.5%, 25%, 50% and 25% negated
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/*pulse channel registers ($4000-$4003 for pulse 1, $4004-$4007 for pulse 2)
+r0  DDLC VVVV   duty, length counter halt / envelope loop, constant volume, volume/envelope period
+r1  EPPP NSSS   sweep enable, sweep period, negate, shift
+r2  TTTT TTTT   timer low 8 bits
+r3  LLLL LTTT   length counter load, timer high 3 bits*/
+#[derive(Clone)]
+pub struct Pulse {
+    //pulse 1 negates its sweep with ones' complement (adds an extra -1), pulse 2 with twos'
+    pub ones_complement: bool,
+    pub duty: u8,
+    //counts down through the duty table, one step every time the timer runs out
+    pub sequence_step: u8,
+    //11 bit period and the divider counting it down, clocked every apu cycle (2 cpu cycles)
+    pub timer_period: u16,
+    pub timer: u16,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+
+    pub sweep_enabled: bool,
+    pub sweep_period: u8,
+    pub sweep_negate: bool,
+    pub sweep_shift: u8,
+    pub sweep_divider: u8,
+    pub sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Pulse {
+            ones_complement,
+            duty: 0,
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+            length: LengthCounter::default(),
+            envelope: Envelope::default(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    //reg is 0-3, the offset from the channel's first register
+    pub fn write(&mut self, reg: u16, byte: u8) {
+        match reg {
+            0 => {
+                self.duty = byte >> 6;
+                self.length.halt = (byte & 0b0010_0000) != 0;
+                self.envelope.write(byte);
+            }
+            1 => {
+                self.sweep_enabled = (byte & 0b1000_0000) != 0;
+                self.sweep_period = (byte >> 4) & 0b111;
+                self.sweep_negate = (byte & 0b0000_1000) != 0;
+                self.sweep_shift = byte & 0b111;
+                self.sweep_reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | byte as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((byte & 0b111) as u16) << 8;
+                self.length.load(byte >> 3);
+                //restarts the waveform and the envelope, but the timer keeps counting
+                self.sequence_step = 0;
+                self.envelope.start = true;
+            }
+            _ => unreachable!("pulse channels only have 4 registers"),
+        }
+    }
+
+    //once per apu cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = self.sequence_step.wrapping_sub(1) & 0b111;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    //the period the sweep unit wants to move to. this is calculated all the time,
+    //not just when the sweep is clocked, since it can mute the channel either way
+    pub fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            let change = change + self.ones_complement as u16;
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    //periods under 8 (ultrasonic) and sweeps past $7FF silence the channel,
+    //even when the sweep unit is disabled
+    pub fn sweep_muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    //half frame
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0
+            && self.sweep_enabled
+            && self.sweep_shift != 0
+            && !self.sweep_muted()
+        {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    //0-15
+    pub fn output(&self) -> u8 {
+        if !self.length.active()
+            || self.sweep_muted()
+            || DUTY_TABLE[self.duty as usize][self.sequence_step as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.volume()
+        }
+    }
+}
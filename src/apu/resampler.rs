@@ -0,0 +1,98 @@
+use std::f64::consts::PI;
+
+//kernel width in output samples, and how finely we slice up the space between two samples
+const TAPS: usize = 16;
+const PHASES: usize = 32;
+//where the kernel cuts off, as a fraction of the output sample rate. a bit under nyquist
+//so the rolloff finishes before anything can alias
+const CUTOFF: f64 = 0.45;
+
+/*band limited synthesis (the same idea as blargg's blip_buf). the apu output is a bunch of flat
+steps at ~1.79mhz, and just picking every 37th value would alias all over the place. instead,
+every time the level changes we drop a band limited impulse of that size into the output at the
+exact (fractional) sample position it happened, and integrating the buffer turns those impulses
+back into (band limited) steps. the cost is per level change instead of per cpu cycle*/
+#[derive(Clone)]
+pub struct Resampler {
+    //output samples per input clock
+    ratio: f64,
+    //where the next input clock lands, in output samples from the start of buf
+    time: f64,
+    //impulses waiting to be integrated
+    buf: Vec<f32>,
+    //a windowed sinc impulse for every phase offset
+    kernel: Vec<[f32; TAPS]>,
+    last_level: f32,
+    integrator: f32,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        let kernel = (0..=PHASES)
+            .map(|phase| {
+                let offset = phase as f64 / PHASES as f64;
+                let mut taps = [0.0; TAPS];
+                for (i, tap) in taps.iter_mut().enumerate() {
+                    let x = i as f64 - (TAPS / 2) as f64 - offset;
+                    let sinc = if x == 0.0 {
+                        1.0
+                    } else {
+                        (2.0 * PI * CUTOFF * x).sin() / (2.0 * PI * CUTOFF * x)
+                    };
+                    //blackman window over the width of the kernel
+                    let w = 2.0 * PI * x / TAPS as f64;
+                    let window = 0.42 + 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+                    *tap = (sinc * window) as f32;
+                }
+                //every impulse has to add up to exactly 1 or the steps come out the wrong height
+                let sum: f32 = taps.iter().sum();
+                taps.iter_mut().for_each(|t| *t /= sum);
+                taps
+            })
+            .collect();
+
+        Resampler {
+            ratio: sample_rate / clock_rate,
+            time: 0.0,
+            buf: vec![0.0; TAPS + 1],
+            kernel,
+            last_level: 0.0,
+            integrator: 0.0,
+        }
+    }
+
+    //one input clock at this level
+    pub fn clock(&mut self, level: f32) {
+        let delta = level - self.last_level;
+        if delta != 0.0 {
+            self.last_level = level;
+            let index = self.time as usize;
+            let phase = ((self.time - index as f64) * PHASES as f64).round() as usize;
+            if self.buf.len() < index + TAPS + 1 {
+                self.buf.resize(index + TAPS + 1, 0.0);
+            }
+            for (i, tap) in self.kernel[phase].iter().enumerate() {
+                self.buf[index + i] += delta * tap;
+            }
+        }
+        self.time += self.ratio;
+    }
+
+    //output samples that nothing can land on anymore
+    pub fn available(&self) -> usize {
+        self.time as usize
+    }
+
+    //integrate every finished sample into out
+    pub fn read(&mut self, out: &mut Vec<f32>) {
+        let count = self.available();
+        if self.buf.len() < count + TAPS + 1 {
+            self.buf.resize(count + TAPS + 1, 0.0);
+        }
+        for impulse in self.buf.drain(..count) {
+            self.integrator += impulse;
+            out.push(self.integrator);
+        }
+        self.time -= count as f64;
+    }
+}
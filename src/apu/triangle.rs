@@ -0,0 +1,88 @@
+use super::units::LengthCounter;
+
+//the triangle steps down 15 -> 0 and back up 0 -> 15
+const TRIANGLE_TABLE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/*triangle channel registers
+$4008  CRRR RRRR   length counter halt / linear counter control, linear counter reload value
+$4009  ---- ----   unused
+$400A  TTTT TTTT   timer low 8 bits
+$400B  LLLL LTTT   length counter load, timer high 3 bits*/
+#[derive(Clone)]
+pub struct Triangle {
+    pub sequence_step: u8,
+    //unlike the pulses, the triangle timer runs at the full cpu clock
+    pub timer_period: u16,
+    pub timer: u16,
+    pub length: LengthCounter,
+    //a second, finer grained length counter clocked every quarter frame
+    pub linear_counter: u8,
+    pub linear_reload_value: u8,
+    pub linear_reload: bool,
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Triangle {
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+            length: LengthCounter::default(),
+            linear_counter: 0,
+            linear_reload_value: 0,
+            linear_reload: false,
+        }
+    }
+
+    //reg is 0-3, the offset from $4008
+    pub fn write(&mut self, reg: u16, byte: u8) {
+        match reg {
+            0 => {
+                self.length.halt = (byte & 0b1000_0000) != 0;
+                self.linear_reload_value = byte & 0b0111_1111;
+            }
+            1 => {}
+            2 => self.timer_period = (self.timer_period & 0x0700) | byte as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((byte & 0b111) as u16) << 8;
+                self.length.load(byte >> 3);
+                self.linear_reload = true;
+            }
+            _ => unreachable!("the triangle channel only has 4 registers"),
+        }
+    }
+
+    //once per cpu cycle. the waveform only moves while both counters are nonzero,
+    //so silencing the triangle freezes it wherever it was instead of dropping to 0
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.sequence_step = (self.sequence_step + 1) & 0x1F;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    //quarter frame
+    pub fn clock_linear(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        //the control flag doubles as the length counter halt
+        if !self.length.halt {
+            self.linear_reload = false;
+        }
+    }
+
+    //0-15
+    pub fn output(&self) -> u8 {
+        TRIANGLE_TABLE[self.sequence_step as usize]
+    }
+}
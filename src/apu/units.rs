@@ -0,0 +1,89 @@
+//the little pieces every channel is built out of
+
+//length counter load values, indexed by the top 5 bits of the channel's last register
+pub const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+//counts down once per half frame and silences the channel when it hits 0
+#[derive(Clone, Default)]
+pub struct LengthCounter {
+    pub counter: u8,
+    //$4015 enable bit for this channel. disabling clears the counter and blocks loads
+    pub enabled: bool,
+    //doubles as the envelope loop flag on pulse/noise and the linear counter control on triangle
+    pub halt: bool,
+}
+
+impl LengthCounter {
+    pub fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(index & 0x1F) as usize];
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.counter > 0
+    }
+}
+
+//volume envelope shared by the pulses and noise. either a constant volume, or a sawtooth
+//that decays from 15 to 0 (and optionally loops) once every (period + 1) quarter frames
+#[derive(Clone, Default)]
+pub struct Envelope {
+    pub start: bool,
+    pub looping: bool,
+    pub constant: bool,
+    //the volume in constant mode, the divider period otherwise
+    pub period: u8,
+    pub divider: u8,
+    pub decay: u8,
+}
+
+impl Envelope {
+    //the low 6 bits of $4000/$4004/$400C: --LC VVVV
+    pub fn write(&mut self, byte: u8) {
+        self.looping = (byte & 0b0010_0000) != 0;
+        self.constant = (byte & 0b0001_0000) != 0;
+        self.period = byte & 0x0F;
+    }
+
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.period;
+        } else if self.divider == 0 {
+            self.divider = self.period;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn volume(&self) -> u8 {
+        if self.constant {
+            self.period
+        } else {
+            self.decay
+        }
+    }
+}
@@ -1,7 +1,6 @@
-use crate::cart::Cart;
+use crate::cart::Mirroring;
 use crate::nes::NES;
 use crate::ppu::Ppu;
-use crate::vram::Vram;
 impl NES {
     //memory operations
 
@@ -17,6 +16,7 @@ impl NES {
 
     //so when the cpu reads or writes to an address, these functions should dispatch the rw to
     //the appropriate part
+    //every byte the cpu reads or writes is one cpu cycle, so the clock ticks before each access
     pub fn read(&mut self, addr: u16, length: usize) -> Vec<u8> {
         for a in addr as usize..=(addr as usize + length) {
             if self.watchpoints.contains(&(a as usize)) {
@@ -24,6 +24,54 @@ impl NES {
             }
         }
 
+        for _ in 0..length {
+            self.tick();
+            //the dmc can only steal the bus on a read cycle
+            if self.apu.dmc.dma_request().is_some() {
+                self.dmc_dma(addr);
+            }
+        }
+        self.bus_read(addr, length)
+    }
+
+    //the dmc ran out of sample and the cpu has to go fetch the next byte for it. the cpu halts on
+    //a read and keeps repeating that read (side effects and all, which is how dmc playback double
+    //clocks controllers and eats PPUDATA reads) until the dma gets the bus: halt, dummy,
+    //an alignment cycle if we're on the wrong apu half, then the fetch. 3 or 4 cycles.
+    //during OAM DMA the fetch just slots in between sprite bytes for 2
+    pub fn dmc_dma(&mut self, halted_addr: u16) {
+        let sample_addr = match self.apu.dmc.dma_request() {
+            Some(a) => a,
+            None => return,
+        };
+
+        let mut stall = 0;
+        if self.oam_dma_active {
+            self.tick();
+            stall += 1;
+        } else {
+            let repeats = if !self.apu.cycles.is_multiple_of(2) {
+                3
+            } else {
+                2
+            };
+            for _ in 0..repeats {
+                self.tick();
+                self.bus_read(halted_addr, 1);
+                stall += 1;
+            }
+        }
+
+        //samples always come from $8000-$FFFF, so this goes through the mapper
+        self.tick();
+        let byte = self.bus_read(sample_addr, 1)[0];
+        self.apu.dmc.fill_buffer(byte);
+        stall += 1;
+
+        self.cycles += stall;
+    }
+
+    fn bus_read(&mut self, addr: u16, length: usize) -> Vec<u8> {
         match addr {
             //WRAM(2kb) + 3 mirrors
             0x0000..=0x1FFF => {
@@ -36,35 +84,31 @@ impl NES {
             0x2000..=0x3FFF => {
                 let final_addr = (addr - 0x2000) % 8;
 
-                return match final_addr {
-                    0x0 => vec![self.ppu.regs.PPUCTRL.into()],
-                    0x1 => vec![self.ppu.regs.PPUMASK.into()],
-                    0x2 => vec![self.ppu.regs.PPUSTATUS.into()],
-                    0x3 => vec![self.ppu.regs.OAMADDR],
-                    0x4 => vec![self.ppu.regs.OAMDATA],
-                    0x5 => vec![self.ppu.regs.PPUSCROLL],
-                    0x6 => vec![self.ppu.regs.PPUADDR],
-                    0x7 => vec![self.ppu.regs.PPUDATA],
-                    _ => unreachable! {"TRIED TO READ A PPU CONTROL REG THAT DOESNT EXIST"},
-                };
+                return vec![self.ppu.reg_read(final_addr)];
             }
             //registers (apu and io)
-            0x4000..=0x4017 => {
-                return vec![0; 1];
-                //unimplemented!("tried to read apu/io regs")
-            }
+            0x4000..=0x4017 => match addr {
+                0x4015 => vec![self.apu.read_status()],
+                //everything else is write only (or not hooked up yet)
+                _ => vec![0; 1],
+            },
             //cart expansion
             0x4018..=0x5FFF => {
                 //return [].into();
                 unimplemented!("tried to read cart expansion")
             }
-            //cart SRAM (8k)
-            0x6000..=0x7FFF => {
-                //return [].into();
-                unimplemented!("tried to read cart SRAM")
-            }
-            //PRG-ROM (32K)
-            0x8000..=0xFFFF => self.ppu.cart.cpu_read(addr, length),
+            //cart SRAM (8k) and PRG-ROM (32K), the mapper decides where these land
+            0x6000..=0xFFFF => self.ppu.cart.cpu_read(addr, length),
+        }
+    }
+    //reads without side effects or ticking the clock, so logging/debugging doesnt disturb the
+    //hardware. the ppu registers and $4015 are the only things on the bus that care so far
+    pub fn peek(&mut self, addr: u16, length: usize) -> Vec<u8> {
+        match addr {
+            0x2000..=0x3FFF => vec![self.ppu.reg_peek((addr - 0x2000) % 8)],
+            //a real $4015 read acks the frame irq
+            0x4015 => vec![self.apu.peek_status()],
+            _ => self.bus_read(addr, length),
         }
     }
     pub fn write(&mut self, addr: u16, bytes: &Vec<u8>) {
@@ -74,6 +118,10 @@ impl NES {
             }
         }*/
 
+        for _ in 0..bytes.len() {
+            self.tick();
+        }
+
         match addr {
             //WRAM(2kb) + 3 mirrors
             0x0000..=0x1FFF => {
@@ -88,33 +136,36 @@ impl NES {
             0x2000..=0x3FFF => {
                 let final_addr = (addr - 0x2000) % 8;
 
-                match final_addr {
-                    0x0 => self.ppu.regs.PPUCTRL = bytes[0].into(),
-                    0x1 => self.ppu.regs.PPUMASK = bytes[0].into(),
-                    0x2 => self.ppu.regs.PPUSTATUS = bytes[0].into(),
-                    0x3 => self.ppu.regs.OAMADDR = bytes[0],
-                    0x4 => self.ppu.regs.OAMDATA = bytes[0],
-                    0x5 => self.ppu.regs.PPUSCROLL = bytes[0],
-                    0x6 => self.ppu.regs.PPUADDR = bytes[0],
-                    0x7 => self.ppu.regs.PPUDATA = bytes[0],
-                    _ => unreachable! {"TRIED TO WRITE A PPU CONTROL REG THAT DOESNT EXIST"},
-                };
+                self.ppu.reg_write(final_addr, bytes[0]);
             }
             //registers (apu and io)
             0x4000..=0x4017 => {
-                unimplemented!("tried to wrote to apu/io regs")
+                match addr {
+                    0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write_reg(addr, bytes[0]),
+                    //OAM DMA, copies the page the cpu wrote here into OAM. the actual
+                    //copy (and the cpu stall) happens once the current instruction is done.
+                    0x4014 => {
+                        self.ppu.regs.OAMDMA = bytes[0];
+                        self.dma_page = Some(bytes[0]);
+                    }
+                    //the rest of the apu/io regs arent hooked up yet
+                    _ => {}
+                }
             }
             //cart expansion
             0x4018..=0x5FFF => {
                 unimplemented!("tried to write to cart expansion?")
             }
-            //cart SRAM (8k)
-            0x6000..=0x7FFF => {
-                unimplemented!("tried to write to cart SRAM")
-            }
-            //PRG-ROM (32K)
-            0x8000..=0xFFFF => {
-                unimplemented!("tried to write to prg-rom??")
+            //cart SRAM (8k) and PRG-ROM (32K)
+            //mapper registers live up here, the cart decides what a write actually does
+            0x6000..=0xFFFF => {
+                for (i, b) in bytes.iter().enumerate() {
+                    //the clock already ticked for every byte, so this one landed a few cycles back
+                    let cycle = self.ticks - (bytes.len() - 1 - i) as u128;
+                    self.ppu
+                        .cart
+                        .cpu_write(addr.wrapping_add(i as u16), *b, cycle);
+                }
             }
         }
     }
@@ -150,66 +201,84 @@ $3F00-3FFF is not configurable, always mapped to the internal palette control. *
 impl Ppu {
     pub fn read(&mut self, addr: u16, len: usize) -> Vec<u8> {
         //unimplemented!("NO READING FROM PPU YET!");
+        //the cart is wired to the whole ppu address bus, not just the pattern tables
+        self.cart.ppu_bus(addr, self.cycles);
         match addr {
             0x0000..=0x1FFF => {
                 //goes to cart. mapping nightmares ensue
                 //panic!("ppu tried to read from cart")
                 return self.cart.ppu_read(addr, len);
             }
-            0x2000..=0x2FFF => {
-                //VRAM! 2k
-
-                //because addresses in vram are simply the addresses in the ppu space - 0x2000
-                let final_addr = addr - 0x2000;
-                return self.vram.contents
-                    [final_addr as usize..(final_addr as usize + len) as usize]
-                    .into();
-            }
-            0x3000..=0x3EFF => {
-                //mirror of VRAM
-                let final_addr = addr - 0x3000;
-                return self.vram.contents
-                    [final_addr as usize..(final_addr as usize + len) as usize]
-                    .into();
+            0x2000..=0x3EFF => {
+                //VRAM! 2k, spread over 4 nametables (and mirrored again at $3000) however the cart likes
+                return (0..len)
+                    .map(|i| *self.nametable_mut(addr + i as u16))
+                    .collect();
             }
             0x3F00..=0x3FFF => {
                 //internal palette control
-                panic!("ppu tried to read from internal paletter control")
+                return (0..len)
+                    .map(|i| self.palette_read(addr + i as u16))
+                    .collect();
             }
             _ => panic!("reading from bad ppu addr"),
         }
 
         //return vec![0];
     }
-    pub fn write(&mut self, addr: u16, bytes: &Vec<u8>) {
+    pub fn write(&mut self, addr: u16, bytes: &[u8]) {
         //unimplemented!("NO WRITING FROM PPU YET!")
+        self.cart.ppu_bus(addr, self.cycles);
         match addr {
             0x0000..=0x1FFF => {
                 //goes to cart. mapping nightmares ensue
                 //panic!("ppu tried to write to cart")
-                for byte in bytes {
-                    self.cart.ppu_write(addr, *byte);
-                }
-            }
-            0x2000..=0x2FFF => {
-                //VRAM!
-                let final_addr = addr - 0x2000;
                 for (i, byte) in bytes.iter().enumerate() {
-                    self.vram.contents[final_addr as usize + i] = *byte;
+                    self.cart.ppu_write(addr + i as u16, *byte);
                 }
             }
-            0x3000..=0x3EFF => {
-                //mirror of VRAM (notice youre missing the last few bytes lol)
-                let final_addr = addr - 0x3000;
+            0x2000..=0x3EFF => {
+                //VRAM!
                 for (i, byte) in bytes.iter().enumerate() {
-                    self.vram.contents[final_addr as usize + i] = *byte;
+                    *self.nametable_mut(addr + i as u16) = *byte;
                 }
             }
             0x3F00..=0x3FFF => {
                 //internal palette control
-                panic!("ppu tried to write to internal palette control")
+                for (i, byte) in bytes.iter().enumerate() {
+                    self.palette_write(addr + i as u16, *byte);
+                }
             }
             _ => panic!("reading from bad ppu addr"),
         }
     }
+
+    //maps a nametable address ($2000-$3EFF) to the byte of physical memory behind it
+    fn nametable_mut(&mut self, addr: u16) -> &mut u8 {
+        //$3000-$3EFF is just $2000-$2EFF again
+        let addr = (addr - 0x2000) & 0x0FFF;
+        let table = addr / 0x400;
+        let offset = (addr % 0x400) as usize;
+
+        /*which physical 1kb table each logical table ends up in
+                        $2000 $2400 $2800 $2C00
+        horizontal        A     A     B     B
+        vertical          A     B     A     B
+        single screen A   A     A     A     A
+        single screen B   B     B     B     B
+        four screen       A     B     C     D   (C and D live on the cart)*/
+        let physical = match self.cart.mirroring() {
+            Mirroring::Horizontal => table / 2,
+            Mirroring::Vertical => table % 2,
+            Mirroring::SingleScreenA => 0,
+            Mirroring::SingleScreenB => 1,
+            Mirroring::FourScreen => table,
+        } as usize;
+
+        if physical < 2 {
+            &mut self.vram.contents[physical * 0x400 + offset]
+        } else {
+            &mut self.cart.four_screen_ram[(physical - 2) * 0x400 + offset]
+        }
+    }
 }
@@ -1,76 +1,410 @@
-use core::panic;
+use crate::mapper::{self, Mapper};
 use std::fs;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone)]
 pub struct Cart {
+    //everything we pulled out of the ines header
+    pub header: RomHeader,
     //technically the cart may contain literally anything, but these are the three most common things
     pub prg_rom: Vec<u8>,
     pub prg_ram: Vec<u8>,
-    pub chr_rom: Vec<u8>,
+    //chr rom straight from the file, or blank chr ram if the header says the board has no chr rom
+    pub chr: Vec<u8>,
+    //only chr ram takes writes, the ppu writing to rom just does nothing
+    pub chr_is_ram: bool,
+    //decides what every cpu and ppu access to the cart actually touches
+    pub mapper: Box<dyn Mapper>,
+    //the extra 2kb of nametable ram four screen boards carry
+    pub four_screen_ram: Vec<u8>,
+    //emulate bus conflicts on boards that have them. off by default since
+    //well behaved games write values that already match the rom
+    pub bus_conflicts: bool,
+    //where battery backed prg ram gets saved, the rom's path with a .sav extension.
+    //None if the cart has no battery
+    pub save_path: Option<PathBuf>,
+    //prg ram has been written since the last save
+    pub save_dirty: bool,
 }
 
-//TODO: remove me when we implement not terrible carts lmao
-//NROM128 for now?
+//how the two physical nametables in vram get spread across the four logical ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    //the cart provides the other 2kb, so all four nametables are unique
+    FourScreen,
+    //every nametable shows the first (A) or second (B) physical nametable
+    SingleScreenA,
+    SingleScreenB,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+    //nes 2.0 only, the actual type lives in byte 13
+    Extended(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    Multi,
+    Dendy,
+}
+
+/*iNES header layout (16 bytes)
+0-3   constant $4E $45 $53 $1A ("NES" followed by MS-DOS end-of-file)
+4     size of PRG ROM in 16 KB units
+5     size of CHR ROM in 8 KB units (value 0 means the board uses CHR RAM)
+6     flags 6 - mapper lo nibble, mirroring, battery, trainer
+7     flags 7 - mapper hi nibble, nes 2.0 identifier, console type
+8     ines: PRG RAM size in 8kb units / nes 2.0: mapper msb + submapper
+9     ines: tv system / nes 2.0: PRG/CHR ROM size msb
+10    ines: unofficial / nes 2.0: PRG RAM (lo nibble) and PRG NVRAM (hi nibble) shift counts
+11    nes 2.0: CHR RAM (lo nibble) and CHR NVRAM (hi nibble) shift counts
+12    nes 2.0: cpu/ppu timing
+13    nes 2.0: vs system type / extended console type
+14-15 nes 2.0: misc roms, default expansion device*/
+#[derive(Clone, Debug)]
+pub struct RomHeader {
+    pub mapper: u16,
+    pub submapper: u8,
+    //all sizes are in BYTES, not in banks
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub mirroring: Mirroring,
+    //battery backed (or otherwise persistent) memory is present
+    pub battery: bool,
+    pub trainer: bool,
+    pub console: ConsoleType,
+    pub region: Region,
+}
+
+impl RomHeader {
+    pub fn new(header: &[u8]) -> Result<Self, String> {
+        if header.len() < 16 {
+            return Err(format!("header is only {} bytes long", header.len()));
+        }
+        if header[0..4] != [0x4E, 0x45, 0x53, 0x1A] {
+            return Err("missing NES<EOF> magic, this is not an ines file".to_string());
+        }
+
+        let flags_6 = header[6];
+        let flags_7 = header[7];
+        let nes2 = (flags_7 & 0b0000_1100) == 0b0000_1000;
+
+        let mirroring = if (flags_6 & 0b0000_1000) != 0 {
+            Mirroring::FourScreen
+        } else if (flags_6 & 0b0000_0001) != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let battery = (flags_6 & 0b0000_0010) != 0;
+        let trainer = (flags_6 & 0b0000_0100) != 0;
+
+        let console = match flags_7 & 0b0000_0011 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(if nes2 { header[13] & 0x0F } else { 0 }),
+        };
+
+        let mapper_lo = (flags_6 >> 4) as u16;
+
+        if nes2 {
+            let mapper = mapper_lo | (flags_7 & 0xF0) as u16 | ((header[8] & 0x0F) as u16) << 8;
+            let submapper = header[8] >> 4;
+
+            let prg_rom_size = nes2_rom_size(header[4], header[9] & 0x0F, 16384)?;
+            let chr_rom_size = nes2_rom_size(header[5], header[9] >> 4, 8192)?;
+
+            let region = match header[12] & 0b0000_0011 {
+                0 => Region::Ntsc,
+                1 => Region::Pal,
+                2 => Region::Multi,
+                _ => Region::Dendy,
+            };
+
+            Ok(RomHeader {
+                mapper,
+                submapper,
+                prg_rom_size,
+                chr_rom_size,
+                prg_ram_size: nes2_ram_size(header[10] & 0x0F),
+                prg_nvram_size: nes2_ram_size(header[10] >> 4),
+                chr_ram_size: nes2_ram_size(header[11] & 0x0F),
+                chr_nvram_size: nes2_ram_size(header[11] >> 4),
+                mirroring,
+                battery,
+                trainer,
+                console,
+                region,
+            })
+        } else {
+            //old dumping tools liked to scribble their name ("DiskDude!") over bytes 7-15.
+            //if the tail of the header isnt zeroed out, the upper mapper nibble is garbage
+            let mapper = if header[12..16].iter().any(|b| *b != 0) {
+                mapper_lo
+            } else {
+                mapper_lo | (flags_7 & 0xF0) as u16
+            };
+
+            let chr_rom_size = header[5] as usize * 8192;
+            //a value of 0 means 8kb for compatibility's sake
+            let prg_ram_size = header[8].max(1) as usize * 8192;
+
+            Ok(RomHeader {
+                mapper,
+                submapper: 0,
+                prg_rom_size: header[4] as usize * 16384,
+                chr_rom_size,
+                //plain ines has no way to say the ram is battery backed besides the flag
+                prg_ram_size: if battery { 0 } else { prg_ram_size },
+                prg_nvram_size: if battery { prg_ram_size } else { 0 },
+                //no chr rom means the board has 8kb of chr ram instead
+                chr_ram_size: if chr_rom_size == 0 { 8192 } else { 0 },
+                chr_nvram_size: 0,
+                mirroring,
+                battery,
+                trainer,
+                console,
+                region: if (header[9] & 0b0000_0001) != 0 {
+                    Region::Pal
+                } else {
+                    Region::Ntsc
+                },
+            })
+        }
+    }
+
+    //total bytes of prg ram on the board, battery backed or not
+    pub fn prg_ram_total(&self) -> usize {
+        self.prg_ram_size + self.prg_nvram_size
+    }
+
+    //total bytes of chr ram on the board, battery backed or not
+    pub fn chr_ram_total(&self) -> usize {
+        self.chr_ram_size + self.chr_nvram_size
+    }
+
+    //bytes of chr memory the ppu sees, rom or ram. a nes 2.0 header with no chr rom
+    //AND no chr ram is almost always a bad dump of an 8kb chr ram board
+    pub fn chr_size(&self) -> usize {
+        if self.chr_rom_size != 0 {
+            self.chr_rom_size
+        } else if self.chr_ram_total() != 0 {
+            self.chr_ram_total()
+        } else {
+            8192
+        }
+    }
+}
+
+//nes 2.0 rom sizes are either (msb nibble << 8 | lsb) banks, or if the msb nibble is $F,
+//an exponent-multiplier pair packed into the lsb: EEEE EEMM -> 2^E * (MM*2+1) bytes.
+//E goes up to 63, so a garbage header can ask for more memory than there is
+fn nes2_rom_size(lsb: u8, msb: u8, bank_size: usize) -> Result<usize, String> {
+    let size = if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0b0000_0011) as usize * 2 + 1;
+        2usize
+            .checked_pow(exponent)
+            .and_then(|s| s.checked_mul(multiplier))
+    } else {
+        ((msb as usize) << 8 | lsb as usize).checked_mul(bank_size)
+    };
+    size.ok_or_else(|| format!("rom size {msb:X}/{lsb:02X} in the header is way too big"))
+}
+
+//nes 2.0 ram sizes are stored as shift counts, 64 << n bytes, with 0 meaning none at all
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
 
-//NOTE: we should turn this into a trait at some point. a useful abstraction of what
-//a cart really is is simple a cpu read/write mapping, and a ppu read/write mapping
 #[allow(dead_code)]
 impl Cart {
-    pub fn new(filename: &str) -> Self {
-        //TODO: finish actually parsing ines!!
-
+    pub fn new(filename: &str) -> Result<Self, String> {
         //load our rom
-        let rom_raw = fs::read(filename).expect("file not found!");
+        let rom_raw = fs::read(filename).map_err(|e| format!("couldnt read {filename}: {e}"))?;
 
-        let header = rom_raw[0..16].to_vec();
-        //size of prg_rom in 16kb units
-        let prg_rom_size = header[4];
-        //size of chr_rom in 8kb units
-        let chr_rom_size = header[5];
+        let mut header =
+            RomHeader::new(&rom_raw).map_err(|e| format!("could not parse ines header: {e}"))?;
+        //trainers go at $7000, so a board with one always gets the full 8kb at $6000,
+        //whatever the header says. this has to happen before the mapper sees the sizes
+        if header.trainer && header.prg_ram_total() < 0x2000 {
+            header.prg_ram_size = 0x2000 - header.prg_nvram_size;
+        }
 
-        let flags_6 = header[6];
+        //vs system and playchoice boards have extra hardware (coin slots, their own ppus and
+        //palettes) that we dont emulate, and extended types are famiclones with their own quirks
+        match header.console {
+            ConsoleType::Nes => {}
+            ConsoleType::VsSystem => return Err("vs system roms arent supported".to_string()),
+            ConsoleType::Playchoice10 => {
+                return Err("playchoice-10 roms arent supported".to_string())
+            }
+            ConsoleType::Extended(t) => {
+                return Err(format!("extended console type {t} roms arent supported"))
+            }
+        }
+
+        //the trainer (if there is one) sits between the header and prg rom,
+        //and ends up in prg ram at $7000
+        let mut offset = 16;
+        let trainer = if header.trainer {
+            offset += 512;
+            Some(
+                rom_raw
+                    .get(16..offset)
+                    .ok_or("rom ends in the middle of the trainer")?
+                    .to_vec(),
+            )
+        } else {
+            None
+        };
+
+        let prg_rom = rom_raw
+            .get(offset..offset + header.prg_rom_size)
+            .ok_or("rom is shorter than the prg rom size in the header")?
+            .to_vec();
+        offset += header.prg_rom_size;
+
+        let chr_is_ram = header.chr_rom_size == 0;
+        let chr = if chr_is_ram {
+            vec![0; header.chr_size()]
+        } else {
+            rom_raw
+                .get(offset..offset + header.chr_rom_size)
+                .ok_or("rom is shorter than the chr rom size in the header")?
+                .to_vec()
+        };
+
+        let mut prg_ram = vec![0; header.prg_ram_total()];
 
-        let prg_rom = if (flags_6 & 0b0000_0100) != 0 {
-            //we have a trainer
-            panic!("this rom contains a trainer and we dont know how to deal with that yet");
+        //pick up where the last session left off
+        let save_path = if header.battery {
+            Some(Path::new(filename).with_extension("sav"))
         } else {
-            rom_raw[17..(16 + prg_rom_size as usize * 16384) as usize].to_vec()
+            None
         };
+        if let Some(path) = &save_path {
+            if let Ok(save) = fs::read(path) {
+                let len = save.len().min(prg_ram.len());
+                prg_ram[..len].copy_from_slice(&save[..len]);
+            }
+        }
+        //trainers always get loaded at $7000-$71FF, after the save so it cant clobber them
+        if let Some(t) = &trainer {
+            prg_ram[0x1000..0x1200].copy_from_slice(t);
+        }
 
-        let chr_rom = if (flags_6 & 0b0000_0100) != 0 {
-            //we have a trainer
-            panic!("this rom contains a trainer and we dont know how to deal with that yet");
+        let mapper = mapper::new(&header)?;
+        let four_screen_ram = if header.mirroring == Mirroring::FourScreen {
+            vec![0; 2048]
         } else {
-            rom_raw[(16 + prg_rom_size as usize * 16384) + 1 as usize..].to_vec()
+            Vec::new()
         };
 
-        /*let mut init_contents: Vec<u8> = vec![0; 16384];
-        for (i, v) in bank0.iter().enumerate() {
-            init_contents[i] = *v;
-        }*/
-        Cart {
-            //prg_rom: bank0,
-            prg_rom: prg_rom,
-            prg_ram: vec![0; 2048],
-            chr_rom: vec![0; 8192],
+        Ok(Cart {
+            header,
+            prg_rom,
+            prg_ram,
+            chr,
+            chr_is_ram,
+            mapper,
+            four_screen_ram,
+            bus_conflicts: false,
+            save_path,
+            save_dirty: false,
+        })
+    }
+
+    //write battery backed prg ram out to the .sav file, if anything changed since last time
+    pub fn save(&mut self) -> Result<(), String> {
+        if !self.save_dirty {
+            return Ok(());
+        }
+        if let Some(path) = &self.save_path {
+            fs::write(path, &self.prg_ram)
+                .map_err(|e| format!("couldnt write save file {}: {e}", path.display()))?;
         }
+        self.save_dirty = false;
+        Ok(())
     }
 
+    //the cpu can see the cart from $4020 up, but we only care about $6000-$FFFF for now.
+    //$6000-$7FFF is prg ram, $8000-$FFFF is prg rom
     pub fn cpu_read(&mut self, addr: u16, length: usize) -> Vec<u8> {
-        //two mirrors of 16KB each
-        let eff_addr = addr % 16384;
-        self.prg_rom[eff_addr as usize..eff_addr as usize + length].into()
+        (0..length)
+            .map(|i| {
+                let a = addr.wrapping_add(i as u16);
+                match self.mapper.cpu_read(a) {
+                    Some(offset) if a >= 0x8000 => self.prg_rom[offset],
+                    Some(offset) => self.prg_ram[offset],
+                    //nothing drives the bus. we dont emulate open bus yet so just say 0
+                    None => 0,
+                }
+            })
+            .collect()
     }
-    pub fn cpu_write(&mut self, addr: u16, byte: u8) {
-        let eff_addr = addr % 16384;
-        //self.prg_rom[(addr - 0xc000) as usize] = byte;
-        self.prg_rom[eff_addr as usize] = byte;
+    pub fn cpu_write(&mut self, addr: u16, mut byte: u8, cpu_cycle: u128) {
+        //the rom and the cpu both drive the data bus, and the 0s win
+        if self.bus_conflicts && addr >= 0x8000 && self.mapper.bus_conflicts() {
+            if let Some(offset) = self.mapper.cpu_read(addr) {
+                byte &= self.prg_rom[offset];
+            }
+        }
+
+        self.mapper.write_cycle(cpu_cycle);
+        if let Some(offset) = self.mapper.cpu_write(addr, byte) {
+            //rom is rom, so only ram writes actually stick
+            if addr < 0x8000 {
+                self.prg_ram[offset] = byte;
+                self.save_dirty = true;
+            }
+        }
     }
 
     pub fn ppu_read(&mut self, addr: u16, length: usize) -> Vec<u8> {
-        return self.chr_rom[addr as usize..addr as usize + length].to_vec();
+        (0..length)
+            .map(|i| {
+                let offset = self.mapper.ppu_read(addr + i as u16);
+                self.chr[offset]
+            })
+            .collect()
     }
     pub fn ppu_write(&mut self, addr: u16, byte: u8) {
-        self.chr_rom[addr as usize] = byte;
+        //the mapper still gets to see the write, but rom doesnt change
+        if let Some(offset) = self.mapper.ppu_write(addr, byte) {
+            if self.chr_is_ram {
+                self.chr[offset] = byte;
+            }
+        }
+    }
+
+    //lets the mapper see the ppu address bus
+    pub fn ppu_bus(&mut self, addr: u16, ppu_cycle: usize) {
+        self.mapper.ppu_bus(addr, ppu_cycle);
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mapper.mirroring()
+    }
+
+    pub fn irq(&self) -> bool {
+        self.mapper.irq()
     }
 }
@@ -9,8 +9,19 @@ pub struct Cpu {
     pub Y: u8,
     pub SR: SR,
     pub SP: u8,
+    //IRQ input pin. its wired-OR, so every device pulling it low gets its own bit (IRQ_*)
+    //and the line stays asserted until all of them let go
+    pub irq_sources: u8,
+    //the I flag as the interrupt poll sees it. CLI, SEI and PLP change I after the poll for the
+    //next instruction already happened, so their effect on irqs shows up one instruction late
+    pub irq_inhibit: bool,
 }
 
+//irq sources
+pub const IRQ_MAPPER: u8 = 0b0000_0001;
+pub const IRQ_FRAME_COUNTER: u8 = 0b0000_0010;
+pub const IRQ_DMC: u8 = 0b0000_0100;
+
 //this is how we print our cpu status for comparing against nestest
 impl fmt::Display for Cpu {
     //A:00 X:00 Y:00 P:  N:0 V:0 B:10 D:0 I:1 Z:0 C:0  SP:FD  CYC:7
@@ -114,6 +125,8 @@ impl Cpu {
             Y: 0x0,
             SR: SR::new(),
             SP: 0xFD,
+            irq_sources: 0,
+            irq_inhibit: true,
             //WRAM: [0; 2048],
             //mem_channel,
         };
@@ -165,13 +178,26 @@ impl Cpu {
         ret_vec
     }
 
+    //a device asserting (or releasing) its irq
+    pub fn set_irq(&mut self, source: u8, asserted: bool) {
+        if asserted {
+            self.irq_sources |= source;
+        } else {
+            self.irq_sources &= !source;
+        }
+    }
+
+    pub fn irq_line(&self) -> bool {
+        self.irq_sources != 0
+    }
+
     pub fn push(&mut self, wram: &mut crate::wram::Wram, val: u8) {
         wram.contents[self.SP as usize + 0x100] = val;
-        self.SP -= 1;
+        self.SP = self.SP.wrapping_sub(1);
     }
 
     pub fn pop(&mut self, wram: &mut crate::wram::Wram) -> u8 {
-        self.SP += 1;
+        self.SP = self.SP.wrapping_add(1);
         wram.contents[self.SP as usize + 0x100]
     }
 }
@@ -0,0 +1,513 @@
+use crate::nes::{print_bytes, AddrMode, NES};
+use std::fmt::Write;
+
+/*cycle stepped cpu core. instead of running an instruction in one go, every cycle is exactly one
+bus access, in the same order the real 6502 does them (see 6502_cpu.txt). that includes all the
+accesses that dont do anything useful:
+    - implied/accumulator ops read the byte after the opcode and throw it away
+    - zp,X / zp,Y read the unindexed zeropage address first
+    - abs,X / abs,Y / (zp),Y read from the wrong page before fixing the high byte.
+      loads only do this when they actually cross a page, stores and rmw ops always do
+    - read-modify-write ops write the unmodified value back before writing the result
+    - stack ops and JSR/RTS/RTI have dummy reads of the next byte and the stack
+these all hit the bus, so they tick the clock and poke whatever lives at that address
+(PPUSTATUS, PPUDATA, mapper registers, controller strobes...)*/
+
+impl NES {
+    //runs one instruction, a cycle at a time
+    pub fn step_cycles(&mut self) -> Result<String, String> {
+        let start_pc = self.cpu.PC;
+        let start_cycles = self.cycles;
+        let start_ticks = self.ticks;
+
+        let instr = self.peek(self.cpu.PC, 1)[0];
+        let (name, len) = match self.instr_data.instrs.get(&instr) {
+            Some(v) => (v.name.clone(), v.len),
+            None => {
+                unimplemented!(
+                    "crashing on unimplemented op: {instr:02x} at PC = {:04X}, cyc = {}",
+                    self.cpu.PC,
+                    self.cycles
+                )
+            }
+        };
+        //the unofficial ops are marked with a * in the table. the log keeps it, the dispatch doesnt care
+        let op = name.trim_start_matches('*');
+        //grab the bytes for the log before anything changes them
+        let bytes = self.peek(self.cpu.PC, len);
+        let i_before = self.cpu.SR.I;
+
+        //cycle 1 is always the opcode fetch
+        self.fetch_pc();
+
+        match instr {
+            //BRK reads (and skips) its padding byte, then its the usual interrupt sequence
+            0x00 => {
+                self.fetch_pc();
+                self.interrupt(0xFFFE, true);
+            }
+            //JSR: the high byte of the target is fetched last, after PC is already on the stack
+            0x20 => {
+                let lo = self.fetch_pc();
+                self.stack_dummy_read();
+                let pc = self.cpu.PC;
+                self.push_bus((pc >> 8) as u8);
+                self.push_bus((pc & 0xFF) as u8);
+                let hi = self.read(self.cpu.PC, 1)[0];
+                self.cpu.PC = lo as u16 | (hi as u16) << 8;
+            }
+            //RTI
+            0x40 => {
+                self.dummy_read_pc();
+                self.stack_dummy_read();
+                let sr = self.pull_bus();
+                self.set_sr_from_stack(sr);
+                let lo = self.pull_bus() as u16;
+                let hi = self.pull_bus() as u16;
+                self.cpu.PC = lo | hi << 8;
+            }
+            //RTS, PC gets bumped past the JSR's last byte on the final cycle
+            0x60 => {
+                self.dummy_read_pc();
+                self.stack_dummy_read();
+                let lo = self.pull_bus() as u16;
+                let hi = self.pull_bus() as u16;
+                self.cpu.PC = lo | hi << 8;
+                self.fetch_pc();
+            }
+            //JMP abs
+            0x4C => {
+                let lo = self.fetch_pc() as u16;
+                let hi = self.fetch_pc() as u16;
+                self.cpu.PC = lo | hi << 8;
+            }
+            //JMP (ind), with the page wrap bug on the pointer
+            0x6C => {
+                let lo = self.fetch_pc() as u16;
+                let hi = self.fetch_pc() as u16;
+                let ptr = lo | hi << 8;
+                let target_lo = self.read(ptr, 1)[0] as u16;
+                let target_hi =
+                    self.read((ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF), 1)[0] as u16;
+                self.cpu.PC = target_lo | target_hi << 8;
+            }
+            //PHA, PHP
+            0x48 | 0x08 => {
+                self.dummy_read_pc();
+                let val = if instr == 0x48 {
+                    self.cpu.ACC
+                } else {
+                    //PHP always pushes with both B bits set
+                    let mut saved_sr = self.cpu.SR;
+                    saved_sr.BH = true;
+                    saved_sr.BL = true;
+                    saved_sr.decode()
+                };
+                self.push_bus(val);
+            }
+            //PLA, PLP
+            0x68 | 0x28 => {
+                self.dummy_read_pc();
+                self.stack_dummy_read();
+                let val = self.pull_bus();
+                if instr == 0x68 {
+                    self.cpu.ACC = val;
+                    self.set_nz(val);
+                } else {
+                    self.set_sr_from_stack(val);
+                }
+            }
+            //branches
+            0x10 | 0x30 | 0x50 | 0x70 | 0x90 | 0xB0 | 0xD0 | 0xF0 => {
+                let offset = self.fetch_pc() as i8;
+                if self.branch_taken(instr) {
+                    //the next opcode gets fetched (and dropped) while the low byte is added
+                    self.dummy_read_pc();
+                    let target = self.cpu.PC.wrapping_add_signed(offset as i16);
+                    let wrong = (self.cpu.PC & 0xFF00) | (target & 0x00FF);
+                    if wrong != target {
+                        self.read(wrong, 1);
+                    }
+                    self.cpu.PC = target;
+                }
+            }
+            _ => {
+                let mode = addr_mode(instr);
+                match op {
+                    //implied, and the accumulator versions of the shifts
+                    _ if matches!(mode, AddrMode::ACC) => {
+                        self.dummy_read_pc();
+                        self.implied(op);
+                    }
+                    "STA" | "STX" | "STY" | "SAX" => {
+                        let addr = self.effective_addr(mode, true);
+                        let val = match op {
+                            "STA" => self.cpu.ACC,
+                            "STX" => self.cpu.X,
+                            "STY" => self.cpu.Y,
+                            _ => self.cpu.ACC & self.cpu.X,
+                        };
+                        self.write(addr, &vec![val]);
+                    }
+                    "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC" | "SLO" | "RLA" | "SRE"
+                    | "RRA" | "DCP" | "ISB" => {
+                        let addr = self.effective_addr(mode, true);
+                        let val = self.read(addr, 1)[0];
+                        //the cpu writes the old value back while it works out the new one
+                        self.write(addr, &vec![val]);
+                        let new_val = self.rmw(op, val);
+                        self.write(addr, &vec![new_val]);
+                    }
+                    _ => {
+                        let val = if matches!(mode, AddrMode::IMM) {
+                            self.fetch_pc()
+                        } else {
+                            let addr = self.effective_addr(mode, false);
+                            self.read(addr, 1)[0]
+                        };
+                        self.alu_read(op, val);
+                    }
+                }
+            }
+        }
+
+        self.cycles = start_cycles + (self.ticks - start_ticks);
+        let dma_stall = self.end_of_instr(instr, i_before);
+
+        let mut stepstring = String::new();
+        let bytes_string = print_bytes(&bytes);
+        let padding: String = vec![" "; 16 - (bytes_string.len() + 6)].join("");
+        write!(stepstring, "{start_pc:04X}  {bytes_string}{padding}{name} ").unwrap();
+        let final_padding = vec![" "; 48 - stepstring.len()].join("");
+        write!(
+            stepstring,
+            "{final_padding}{} CYC:{}",
+            self.cpu, self.cycles
+        )
+        .unwrap();
+        if let Some(stall) = dma_stall {
+            write!(stepstring, " OAMDMA:{stall}").unwrap();
+        }
+
+        Ok(stepstring)
+    }
+
+    //read the byte at PC and move past it
+    fn fetch_pc(&mut self) -> u8 {
+        let val = self.read(self.cpu.PC, 1)[0];
+        self.cpu.PC = self.cpu.PC.wrapping_add(1);
+        val
+    }
+
+    fn dummy_read_pc(&mut self) {
+        self.read(self.cpu.PC, 1);
+    }
+
+    fn stack_dummy_read(&mut self) {
+        self.read(0x100 | self.cpu.SP as u16, 1);
+    }
+
+    //stack accesses that go over the bus, unlike cpu.push/pop
+    fn push_bus(&mut self, val: u8) {
+        self.write(0x100 | self.cpu.SP as u16, &vec![val]);
+        self.cpu.SP = self.cpu.SP.wrapping_sub(1);
+    }
+
+    fn pull_bus(&mut self) -> u8 {
+        self.cpu.SP = self.cpu.SP.wrapping_add(1);
+        self.read(0x100 | self.cpu.SP as u16, 1)[0]
+    }
+
+    //the B bits arent real, so pulling the status leaves them alone (same as PLP/RTI in instr.rs)
+    fn set_sr_from_stack(&mut self, val: u8) {
+        let old_bh = self.cpu.SR.BH;
+        let old_bl = self.cpu.SR.BL;
+        self.cpu.SR.encode(val);
+        self.cpu.SR.BH = old_bh;
+        self.cpu.SR.BL = old_bl;
+    }
+
+    //operand fetch + address calculation, with the dummy reads each mode does along the way.
+    //stores and rmw ops always take the extra indexed cycle, loads only on a page cross
+    fn effective_addr(&mut self, mode: AddrMode, always_fix: bool) -> u16 {
+        match mode {
+            AddrMode::ZPG => self.fetch_pc() as u16,
+            AddrMode::ZPGX | AddrMode::ZPGY => {
+                let base = self.fetch_pc();
+                self.read(base as u16, 1);
+                let index = if matches!(mode, AddrMode::ZPGX) {
+                    self.cpu.X
+                } else {
+                    self.cpu.Y
+                };
+                base.wrapping_add(index) as u16
+            }
+            AddrMode::ABS => {
+                let lo = self.fetch_pc() as u16;
+                let hi = self.fetch_pc() as u16;
+                lo | hi << 8
+            }
+            AddrMode::ABSX | AddrMode::ABSY => {
+                let lo = self.fetch_pc() as u16;
+                let hi = self.fetch_pc() as u16;
+                let index = if matches!(mode, AddrMode::ABSX) {
+                    self.cpu.X
+                } else {
+                    self.cpu.Y
+                };
+                self.indexed(lo | hi << 8, index, always_fix)
+            }
+            AddrMode::INDX => {
+                let ptr = self.fetch_pc();
+                self.read(ptr as u16, 1);
+                let ptr = ptr.wrapping_add(self.cpu.X);
+                let lo = self.read(ptr as u16, 1)[0] as u16;
+                let hi = self.read(ptr.wrapping_add(1) as u16, 1)[0] as u16;
+                lo | hi << 8
+            }
+            AddrMode::INDY => {
+                let ptr = self.fetch_pc();
+                let lo = self.read(ptr as u16, 1)[0] as u16;
+                let hi = self.read(ptr.wrapping_add(1) as u16, 1)[0] as u16;
+                self.indexed(lo | hi << 8, self.cpu.Y, always_fix)
+            }
+            _ => unreachable!("NO EFFECTIVE ADDRESS FOR THIS ADDRESSING MODE"),
+        }
+    }
+
+    //the low byte gets the index added first, so the cpu reads from the unfixed address
+    //before it knows whether the high byte needs a carry
+    fn indexed(&mut self, base: u16, index: u8, always_fix: bool) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        let wrong = (base & 0xFF00) | (addr & 0x00FF);
+        if wrong != addr || always_fix {
+            self.read(wrong, 1);
+        }
+        addr
+    }
+
+    fn branch_taken(&self, instr: u8) -> bool {
+        match instr {
+            0x10 => !self.cpu.SR.N,
+            0x30 => self.cpu.SR.N,
+            0x50 => !self.cpu.SR.V,
+            0x70 => self.cpu.SR.V,
+            0x90 => !self.cpu.SR.C,
+            0xB0 => self.cpu.SR.C,
+            0xD0 => !self.cpu.SR.Z,
+            _ => self.cpu.SR.Z,
+        }
+    }
+
+    fn set_nz(&mut self, val: u8) {
+        self.cpu.SR.N = (val as i8) < 0;
+        self.cpu.SR.Z = val == 0;
+    }
+
+    fn add_with_carry(&mut self, val: u8) {
+        let acc = self.cpu.ACC;
+        let sum = acc as u16 + val as u16 + self.cpu.SR.C as u16;
+        let res = sum as u8;
+        self.cpu.SR.C = sum > 0xFF;
+        self.cpu.SR.V = (!(acc ^ val) & (acc ^ res) & 0x80) != 0;
+        self.cpu.ACC = res;
+        self.set_nz(res);
+    }
+
+    fn compare(&mut self, reg: u8, val: u8) {
+        self.cpu.SR.C = reg >= val;
+        self.set_nz(reg.wrapping_sub(val));
+    }
+
+    //everything that just reads its operand
+    fn alu_read(&mut self, name: &str, val: u8) {
+        match name {
+            "LDA" => {
+                self.cpu.ACC = val;
+                self.set_nz(val);
+            }
+            "LDX" => {
+                self.cpu.X = val;
+                self.set_nz(val);
+            }
+            "LDY" => {
+                self.cpu.Y = val;
+                self.set_nz(val);
+            }
+            "LAX" => {
+                self.cpu.ACC = val;
+                self.cpu.X = val;
+                self.set_nz(val);
+            }
+            "AND" => {
+                self.cpu.ACC &= val;
+                self.set_nz(self.cpu.ACC);
+            }
+            "ORA" => {
+                self.cpu.ACC |= val;
+                self.set_nz(self.cpu.ACC);
+            }
+            "EOR" => {
+                self.cpu.ACC ^= val;
+                self.set_nz(self.cpu.ACC);
+            }
+            "ADC" => self.add_with_carry(val),
+            //subtracting is just adding the complement
+            "SBC" => self.add_with_carry(!val),
+            "CMP" => self.compare(self.cpu.ACC, val),
+            "CPX" => self.compare(self.cpu.X, val),
+            "CPY" => self.compare(self.cpu.Y, val),
+            "BIT" => {
+                self.cpu.SR.Z = (self.cpu.ACC & val) == 0;
+                self.cpu.SR.N = (val & 0x80) != 0;
+                self.cpu.SR.V = (val & 0x40) != 0;
+            }
+            //the unofficial nops with operands still do the read
+            "NOP" => {}
+            _ => unreachable!("{name} isnt a read instruction"),
+        }
+    }
+
+    //read-modify-write ops, returns the value that gets written back
+    fn rmw(&mut self, name: &str, val: u8) -> u8 {
+        match name {
+            "ASL" | "SLO" => {
+                self.cpu.SR.C = (val & 0x80) != 0;
+                let res = val << 1;
+                self.set_nz(res);
+                if name == "SLO" {
+                    self.alu_read("ORA", res);
+                }
+                res
+            }
+            "LSR" | "SRE" => {
+                self.cpu.SR.C = (val & 0x01) != 0;
+                let res = val >> 1;
+                self.set_nz(res);
+                if name == "SRE" {
+                    self.alu_read("EOR", res);
+                }
+                res
+            }
+            "ROL" | "RLA" => {
+                let res = (val << 1) | self.cpu.SR.C as u8;
+                self.cpu.SR.C = (val & 0x80) != 0;
+                self.set_nz(res);
+                if name == "RLA" {
+                    self.alu_read("AND", res);
+                }
+                res
+            }
+            "ROR" | "RRA" => {
+                let res = (val >> 1) | (self.cpu.SR.C as u8) << 7;
+                self.cpu.SR.C = (val & 0x01) != 0;
+                self.set_nz(res);
+                if name == "RRA" {
+                    self.alu_read("ADC", res);
+                }
+                res
+            }
+            "INC" | "ISB" => {
+                let res = val.wrapping_add(1);
+                self.set_nz(res);
+                if name == "ISB" {
+                    self.alu_read("SBC", res);
+                }
+                res
+            }
+            "DEC" | "DCP" => {
+                let res = val.wrapping_sub(1);
+                self.set_nz(res);
+                if name == "DCP" {
+                    self.alu_read("CMP", res);
+                }
+                res
+            }
+            _ => unreachable!("{name} isnt a read-modify-write instruction"),
+        }
+    }
+
+    //single byte instructions (after their dummy read)
+    fn implied(&mut self, name: &str) {
+        match name {
+            "CLC" => self.cpu.SR.C = false,
+            "SEC" => self.cpu.SR.C = true,
+            "CLI" => self.cpu.SR.I = false,
+            "SEI" => self.cpu.SR.I = true,
+            "CLD" => self.cpu.SR.D = false,
+            "SED" => self.cpu.SR.D = true,
+            "CLV" => self.cpu.SR.V = false,
+            "INX" => {
+                self.cpu.X = self.cpu.X.wrapping_add(1);
+                self.set_nz(self.cpu.X);
+            }
+            "INY" => {
+                self.cpu.Y = self.cpu.Y.wrapping_add(1);
+                self.set_nz(self.cpu.Y);
+            }
+            "DEX" => {
+                self.cpu.X = self.cpu.X.wrapping_sub(1);
+                self.set_nz(self.cpu.X);
+            }
+            "DEY" => {
+                self.cpu.Y = self.cpu.Y.wrapping_sub(1);
+                self.set_nz(self.cpu.Y);
+            }
+            "TAX" => {
+                self.cpu.X = self.cpu.ACC;
+                self.set_nz(self.cpu.X);
+            }
+            "TAY" => {
+                self.cpu.Y = self.cpu.ACC;
+                self.set_nz(self.cpu.Y);
+            }
+            "TXA" => {
+                self.cpu.ACC = self.cpu.X;
+                self.set_nz(self.cpu.ACC);
+            }
+            "TYA" => {
+                self.cpu.ACC = self.cpu.Y;
+                self.set_nz(self.cpu.ACC);
+            }
+            "TSX" => {
+                self.cpu.X = self.cpu.SP;
+                self.set_nz(self.cpu.X);
+            }
+            //the only transfer that doesnt touch the flags
+            "TXS" => self.cpu.SP = self.cpu.X,
+            "ASL" | "LSR" | "ROL" | "ROR" => {
+                let acc = self.cpu.ACC;
+                self.cpu.ACC = self.rmw(name, acc);
+            }
+            "NOP" => {}
+            _ => unreachable!("{name} isnt an implied instruction"),
+        }
+    }
+}
+
+//6502 opcodes are laid out as aaabbbcc, and bbb (with cc) mostly decides the addressing mode.
+//ACC here means no operand at all (implied or accumulator). control flow ops get handled
+//by opcode before we ever get here
+fn addr_mode(op: u8) -> AddrMode {
+    let aaa = op >> 5;
+    let bbb = (op >> 2) & 0x07;
+    let cc = op & 0x03;
+    //STX/LDX (cc=10) and SAX/LAX (cc=11) index with Y where everyone else uses X
+    let uses_y = (aaa == 4 || aaa == 5) && cc >= 2;
+
+    match (cc, bbb) {
+        (1 | 3, 0) => AddrMode::INDX,
+        (1 | 3, 2) => AddrMode::IMM,
+        (1 | 3, 4) => AddrMode::INDY,
+        (1 | 3, 6) => AddrMode::ABSY,
+        (0 | 2, 0) => AddrMode::IMM,
+        (_, 1) => AddrMode::ZPG,
+        (_, 3) => AddrMode::ABS,
+        (_, 5) if uses_y => AddrMode::ZPGY,
+        (_, 5) => AddrMode::ZPGX,
+        //SHX/SHA live at the other abs,Y slots, but LDX/LAX abs,Y are the ones we care about
+        (_, 7) if uses_y && aaa == 5 => AddrMode::ABSY,
+        (_, 7) => AddrMode::ABSX,
+        _ => AddrMode::ACC,
+    }
+}
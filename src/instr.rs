@@ -509,25 +509,10 @@ impl crate::NES {
         self.cycles += self.instr_data.instrs[&instr].cycles as u128;
     }
     pub fn BRK(&mut self, instr: u8, bytes: Vec<u8>, stepstring: &mut String) {
-        //interrupt,
-        //push PC+2
-        let ra = self.cpu.PC + 2;
-        self.write(
-            self.cpu.SP as u16,
-            &vec![(ra & 0xFF) as u8, (ra & 0xFF00 >> 8) as u8],
-        );
-        self.cpu.SP -= 2;
-        //SR.I = true
-        self.cpu.SR.I = true;
-        //push SR(B = 11)
-        let mut saved_sr = self.cpu.SR;
-        saved_sr.BH = true;
-        saved_sr.BL = true;
-        self.write(self.cpu.SP as u16, &vec![saved_sr.decode()]);
-        self.cpu.SP -= 1;
-
-        self.cpu.PC += self.instr_data.instrs[&instr].len as u16;
-        self.cycles += self.instr_data.instrs[&instr].cycles as u128;
+        //BRK has a padding byte after it, so the return address is PC+2.
+        //from there its the same sequence as an irq, just with B set in the pushed status
+        self.cpu.PC = self.cpu.PC.wrapping_add(2);
+        self.interrupt(0xFFFE, true);
     }
     pub fn BVC(&mut self, instr: u8, bytes: Vec<u8>, stepstring: &mut String) {
         self.cpu.PC += self.instr_data.instrs[&instr].len as u16;
@@ -665,22 +650,22 @@ impl crate::NES {
         let addr = match instr {
             0xC6 => {
                 let addr = self.calc_addr(&bytes, AddrMode::ZPG, true);
-                self.get_val(&bytes, AddrMode::ZPG, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPG, stepstring);
                 addr
             }
             0xD6 => {
                 let addr = self.calc_addr(&bytes, AddrMode::ZPGX, true);
-                self.get_val(&bytes, AddrMode::ZPGX, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPGX, stepstring);
                 addr
             }
             0xCE => {
                 let addr = self.calc_addr(&bytes, AddrMode::ABS, true);
-                self.get_val(&bytes, AddrMode::ABS, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABS, stepstring);
                 addr
             }
             0xDE => {
                 let addr = self.calc_addr(&bytes, AddrMode::ABSX, true);
-                self.get_val(&bytes, AddrMode::ABSX, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABSX, stepstring);
                 addr
             }
             _ => unreachable!("IN INC, BUT GOT BAD OP"),
@@ -744,22 +729,22 @@ impl crate::NES {
             0xE6 => {
                 let addr = self.calc_addr(&bytes, AddrMode::ZPG, true);
                 //write!(stepstring, "${addr:02X} = ").unwrap();
-                self.get_val(&bytes, AddrMode::ZPG, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPG, stepstring);
                 addr
             }
             0xF6 => {
                 let addr = self.calc_addr(&bytes, AddrMode::ZPGX, true);
-                self.get_val(&bytes, AddrMode::ZPGX, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPGX, stepstring);
                 addr
             }
             0xEE => {
                 let addr = self.calc_addr(&bytes, AddrMode::ABS, true);
-                self.get_val(&bytes, AddrMode::ABS, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABS, stepstring);
                 addr
             }
             0xFE => {
                 let addr = self.calc_addr(&bytes, AddrMode::ABSX, true);
-                self.get_val(&bytes, AddrMode::ABSX, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABSX, stepstring);
                 addr
             }
             _ => unreachable!("IN INC, BUT GOT BAD OP"),
@@ -1116,10 +1101,13 @@ impl crate::NES {
         self.cycles += self.instr_data.instrs[&instr].cycles as u128;
     }
     pub fn RTI(&mut self, instr: u8, bytes: Vec<u8>, stepstring: &mut String) {
+        //same as PLP, the two B bits dont actually exist in the register so they're left alone
         let new_sr = self.cpu.pop(&mut self.wram);
+        let old_bh = self.cpu.SR.BH;
+        let old_bl = self.cpu.SR.BL;
         self.cpu.SR.encode(new_sr);
-        //TODO: THIS IS A FUCKING HACK BC I THINK WE SHOULD ONLY BE ABLE TO GET HERE FROM AN IRQ
-        self.cpu.SR.BH = true;
+        self.cpu.SR.BH = old_bh;
+        self.cpu.SR.BL = old_bl;
 
         let new_pc_lo = self.cpu.pop(&mut self.wram) as u16;
         let new_pc_hi = self.cpu.pop(&mut self.wram) as u16;
@@ -1194,38 +1182,38 @@ impl crate::NES {
         let addr: u16 = match instr {
             0x85 => {
                 let addr = self.calc_addr(&bytes, AddrMode::ZPG, false);
-                self.get_val(&bytes, AddrMode::ZPG, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPG, stepstring);
                 addr
             }
             0x95 => {
                 let addr = self.calc_addr(&bytes, AddrMode::ZPGX, false);
-                self.get_val(&bytes, AddrMode::ZPGX, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPGX, stepstring);
                 addr
             }
             0x8D => {
                 let addr = self.calc_addr(&bytes, AddrMode::ABS, false);
-                self.get_val(&bytes, AddrMode::ABS, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABS, stepstring);
                 addr
             }
             0x9D => {
                 let addr = self.calc_addr(&bytes, AddrMode::ABSX, false);
-                self.get_val(&bytes, AddrMode::ABSX, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABSX, stepstring);
                 addr
             }
             0x99 => {
                 let addr = self.calc_addr(&bytes, AddrMode::ABSY, false);
-                self.get_val(&bytes, AddrMode::ABSY, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABSY, stepstring);
                 addr
             }
             0x81 => {
                 let addr = self.calc_addr(&bytes, AddrMode::INDX, false);
 
-                self.get_val(&bytes, AddrMode::INDX, stepstring, false);
+                self.log_val(&bytes, AddrMode::INDX, stepstring);
                 addr
             }
             0x91 => {
                 let addr = self.calc_addr(&bytes, AddrMode::INDY, false);
-                self.get_val(&bytes, AddrMode::INDY, stepstring, false);
+                self.log_val(&bytes, AddrMode::INDY, stepstring);
                 addr
             }
             _ => {
@@ -1243,16 +1231,16 @@ impl crate::NES {
         //BEFORE we write to it
         let addr = if instr == 0x86 {
             let addr = self.calc_addr(&bytes, AddrMode::ZPG, false);
-            self.get_val(&bytes, AddrMode::ZPG, stepstring, false);
+            self.log_val(&bytes, AddrMode::ZPG, stepstring);
             addr
         } else if instr == 0x96 {
             let addr = self.calc_addr(&bytes, AddrMode::ZPGY, false);
-            self.get_val(&bytes, AddrMode::ZPGY, stepstring, false);
+            self.log_val(&bytes, AddrMode::ZPGY, stepstring);
 
             addr
         } else {
             let addr = self.calc_addr(&bytes, AddrMode::ABS, false);
-            self.get_val(&bytes, AddrMode::ABS, stepstring, false);
+            self.log_val(&bytes, AddrMode::ABS, stepstring);
             addr
         };
 
@@ -1264,15 +1252,15 @@ impl crate::NES {
     pub fn STY(&mut self, instr: u8, bytes: Vec<u8>, stepstring: &mut String) {
         let addr = if instr == 0x84 {
             let addr = self.calc_addr(&bytes, AddrMode::ZPG, false);
-            self.get_val(&bytes, AddrMode::ZPG, stepstring, false);
+            self.log_val(&bytes, AddrMode::ZPG, stepstring);
             addr
         } else if instr == 0x94 {
             let addr = self.calc_addr(&bytes, AddrMode::ZPGX, false);
-            self.get_val(&bytes, AddrMode::ZPGX, stepstring, false);
+            self.log_val(&bytes, AddrMode::ZPGX, stepstring);
             addr
         } else {
             let addr = self.calc_addr(&bytes, AddrMode::ABS, false);
-            self.get_val(&bytes, AddrMode::ABS, stepstring, false);
+            self.log_val(&bytes, AddrMode::ABS, stepstring);
 
             addr
         };
@@ -1392,22 +1380,22 @@ impl crate::NES {
         stepstring.remove(stepstring.len() - 6);
         let addr = match instr {
             0x87 => {
-                self.get_val(&bytes, AddrMode::ZPG, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPG, stepstring);
 
                 self.calc_addr(&bytes, AddrMode::ZPG, false)
             }
             0x97 => {
-                self.get_val(&bytes, AddrMode::ZPGY, stepstring, false);
+                self.log_val(&bytes, AddrMode::ZPGY, stepstring);
 
                 self.calc_addr(&bytes, AddrMode::ZPGY, false)
             }
             0x8F => {
-                self.get_val(&bytes, AddrMode::ABS, stepstring, false);
+                self.log_val(&bytes, AddrMode::ABS, stepstring);
 
                 self.calc_addr(&bytes, AddrMode::ABS, false)
             }
             0x83 => {
-                self.get_val(&bytes, AddrMode::INDX, stepstring, false);
+                self.log_val(&bytes, AddrMode::INDX, stepstring);
 
                 self.calc_addr(&bytes, AddrMode::INDX, false)
             }
@@ -1470,13 +1458,13 @@ impl crate::NES {
         ///////CMP
         //this is only for debug prints
         match instr {
-            0xC7 => self.get_val(&bytes, AddrMode::ZPG, stepstring, false),
-            0xD7 => self.get_val(&bytes, AddrMode::ZPGX, stepstring, false),
-            0xCF => self.get_val(&bytes, AddrMode::ABS, stepstring, false),
-            0xDF => self.get_val(&bytes, AddrMode::ABSX, stepstring, false),
-            0xDB => self.get_val(&bytes, AddrMode::ABSY, stepstring, false),
-            0xC3 => self.get_val(&bytes, AddrMode::INDX, stepstring, false),
-            0xD3 => self.get_val(&bytes, AddrMode::INDY, stepstring, false),
+            0xC7 => self.log_val(&bytes, AddrMode::ZPG, stepstring),
+            0xD7 => self.log_val(&bytes, AddrMode::ZPGX, stepstring),
+            0xCF => self.log_val(&bytes, AddrMode::ABS, stepstring),
+            0xDF => self.log_val(&bytes, AddrMode::ABSX, stepstring),
+            0xDB => self.log_val(&bytes, AddrMode::ABSY, stepstring),
+            0xC3 => self.log_val(&bytes, AddrMode::INDX, stepstring),
+            0xD3 => self.log_val(&bytes, AddrMode::INDY, stepstring),
             _ => unimplemented!("IN IDCP_CMP BUT GOT ILLEGAL OPCODE"),
         };
 
@@ -1521,13 +1509,13 @@ impl crate::NES {
 
         /////////
         match instr {
-            0xE7 => self.get_val(&bytes, AddrMode::ZPG, stepstring, false),
-            0xF7 => self.get_val(&bytes, AddrMode::ZPGX, stepstring, false),
-            0xEF => self.get_val(&bytes, AddrMode::ABS, stepstring, false),
-            0xFF => self.get_val(&bytes, AddrMode::ABSX, stepstring, false),
-            0xFB => self.get_val(&bytes, AddrMode::ABSY, stepstring, false),
-            0xE3 => self.get_val(&bytes, AddrMode::INDX, stepstring, false),
-            0xF3 => self.get_val(&bytes, AddrMode::INDY, stepstring, false),
+            0xE7 => self.log_val(&bytes, AddrMode::ZPG, stepstring),
+            0xF7 => self.log_val(&bytes, AddrMode::ZPGX, stepstring),
+            0xEF => self.log_val(&bytes, AddrMode::ABS, stepstring),
+            0xFF => self.log_val(&bytes, AddrMode::ABSX, stepstring),
+            0xFB => self.log_val(&bytes, AddrMode::ABSY, stepstring),
+            0xE3 => self.log_val(&bytes, AddrMode::INDX, stepstring),
+            0xF3 => self.log_val(&bytes, AddrMode::INDY, stepstring),
             _ => panic!("IN IISB_SBC BUT GOT BAD OPCODE"),
         };
 
@@ -1576,13 +1564,13 @@ impl crate::NES {
 
         //ORA
         match instr {
-            0x07 => self.get_val(&bytes, AddrMode::ZPG, stepstring, false),
-            0x17 => self.get_val(&bytes, AddrMode::ZPGX, stepstring, false),
-            0x0F => self.get_val(&bytes, AddrMode::ABS, stepstring, false),
-            0x1F => self.get_val(&bytes, AddrMode::ABSX, stepstring, false),
-            0x1B => self.get_val(&bytes, AddrMode::ABSY, stepstring, false),
-            0x03 => self.get_val(&bytes, AddrMode::INDX, stepstring, false),
-            0x13 => self.get_val(&bytes, AddrMode::INDY, stepstring, false),
+            0x07 => self.log_val(&bytes, AddrMode::ZPG, stepstring),
+            0x17 => self.log_val(&bytes, AddrMode::ZPGX, stepstring),
+            0x0F => self.log_val(&bytes, AddrMode::ABS, stepstring),
+            0x1F => self.log_val(&bytes, AddrMode::ABSX, stepstring),
+            0x1B => self.log_val(&bytes, AddrMode::ABSY, stepstring),
+            0x03 => self.log_val(&bytes, AddrMode::INDX, stepstring),
+            0x13 => self.log_val(&bytes, AddrMode::INDY, stepstring),
             _ => {
                 panic!("IN OR, BUT GOT INVALID OPCODE")
             }
@@ -7,26 +7,36 @@ use std::{
 };
 
 mod app;
+mod apu;
 mod bus;
 mod cart;
 mod cpu;
+mod cycle;
 mod instr;
+mod mapper;
 mod my_views;
 mod nes;
+mod palette;
 mod ppu;
 mod tui;
 mod vram;
+mod wav;
 mod wram;
 
-use cart::Cart;
+use apu::AudioOutput;
+use cart::{Cart, Region};
 use cpu::Cpu;
 use nes::NES;
+use palette::MasterPalette;
 use ppu::Ppu;
 use wram::Wram;
 
 //use pretty_assertions::Comparison;
 use std::thread;
 
+//flags that take the next arg as their value, so it cant be the rom filename
+const VALUE_FLAGS: [&str; 4] = ["--palette", "--sample-rate", "--wav", "--frames"];
+
 fn main() {
     //loading our log
     let good_log = "./test-roms/nestest-redux/nestest_cpu_relined.log";
@@ -45,32 +55,114 @@ fn main() {
 
     //make our wram
     let wram = Wram::new();
-    //make our "cart"
-    let filename = "./test-roms/nestest/nestest.nes";
-    let cart = Cart::new(filename);
+    //make our "cart". any ines/nes 2.0 rom can be passed as an arg, nestest by default
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let filename = args
+        .iter()
+        .enumerate()
+        .find(|(i, a)| {
+            !a.starts_with("--") && (*i == 0 || !VALUE_FLAGS.contains(&args[i - 1].as_str()))
+        })
+        .map(|(_, a)| a)
+        .cloned()
+        .unwrap_or_else(|| "./test-roms/nestest/nestest.nes".to_string());
+    let mut cart = match Cart::new(&filename) {
+        Ok(cart) => cart,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    cart.bus_conflicts = args.iter().any(|a| a == "--bus-conflicts");
     //ppu and app need a channel to send frame data
     let (tx, rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = channel();
     //make our ppu
-    let ppu = Ppu::new(cart, tx);
+    let mut ppu = Ppu::new(cart, tx);
+    //--palette some.pal swaps out the built in colors
+    if let Some(i) = args.iter().position(|a| a == "--palette") {
+        let palette = args
+            .get(i + 1)
+            .ok_or_else(|| "--palette needs a .pal file after it".to_string())
+            .and_then(|pal_file| MasterPalette::from_file(pal_file));
+        ppu.master_palette = match palette {
+            Ok(palette) => palette,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        };
+    }
 
-    //make our full system and add a breakpoint at the test rom entry address
+    //make our full system, power it on, and add a breakpoint at the rom's entry address
     let mut nes = NES::new(cpu, wram, ppu);
-    let reset_addr = nes.ppu.cart.cpu_read(0xFFFC, 2);
-    nes.cpu.PC = reset_addr[0] as u16 | (reset_addr[1] as u16) << 8;
-    //panic!("reset addr is {:04X}", nes.cpu.PC);
+    //pal consoles run the ppu 3.2 dots per cpu cycle instead of 3, and 312 lines a frame
+    nes.pal = nes.ppu.cart.header.region == Region::Pal || args.iter().any(|a| a == "--pal");
+    //run instructions a bus access at a time, dummy reads and all
+    nes.cycle_stepped = args.iter().any(|a| a == "--cycle-stepped");
+    nes.power_on();
+    //audio output rate, for both the speakers and --wav. 48khz unless --sample-rate says otherwise
+    let sample_rate = match args.iter().position(|a| a == "--sample-rate") {
+        //0hz would mean 0 sample chunks and filters that divide by zero
+        Some(i) => match args.get(i + 1).and_then(|r| r.parse::<u32>().ok()) {
+            Some(rate) if rate > 0 => rate,
+            _ => {
+                eprintln!("--sample-rate needs a rate in hz after it");
+                std::process::exit(1);
+            }
+        },
+        None => 48000,
+    };
+
+    //--wav out.wav runs headless for --frames frames (10 seconds by default) and dumps the
+    //audio instead of opening a window. --wav-channels writes every channel on its own too
+    if let Some(i) = args.iter().position(|a| a == "--wav") {
+        let path = match args.get(i + 1) {
+            Some(path) => path,
+            None => {
+                eprintln!("--wav needs a .wav file after it");
+                std::process::exit(1);
+            }
+        };
+        let frames = match args.iter().position(|a| a == "--frames") {
+            Some(i) => match args.get(i + 1).and_then(|f| f.parse::<usize>().ok()) {
+                Some(frames) => frames,
+                None => {
+                    eprintln!("--frames needs a frame count after it");
+                    std::process::exit(1);
+                }
+            },
+            None => 600,
+        };
+        let per_channel = args.iter().any(|a| a == "--wav-channels");
+        //nobody is going to look at the frames
+        drop(rx);
+        let result = wav::dump(&mut nes, path, frames, sample_rate, per_channel)
+            .and_then(|_| nes.ppu.cart.save());
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let (audio_tx, audio_rx): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = channel();
+    nes.apu.output = Some(AudioOutput::new(nes.pal, sample_rate, Some(audio_tx)));
     nes.add_breakpoint(nes.cpu.PC as usize);
     //nes.add_breakpoint(0xC689);
     //nes.add_breakpoint(0xC6C8);
     //nes.add_breakpoint(0xC5FD);
 
+    //app tells the runner when the window closes so it can write out the save file
+    let (quit_tx, quit_rx): (Sender<()>, Receiver<()>) = channel();
+
     let runner_handle = thread::Builder::new()
         .name("runner".to_string())
-        .spawn(move || nes.run(log.clone()))
+        .spawn(move || nes.run(log.clone(), quit_rx))
         .unwrap();
 
     let window_handle = thread::Builder::new()
         .name("app".to_string())
-        .spawn(move || crate::app::run(rx))
+        .spawn(move || crate::app::run(rx, audio_rx, sample_rate, quit_tx))
         .unwrap();
 
     runner_handle.join().expect("runner thread panicked");
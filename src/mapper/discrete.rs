@@ -0,0 +1,314 @@
+use super::Mapper;
+use crate::cart::{Mirroring, RomHeader};
+
+//the discrete logic boards. each of these is just a latch or two hanging off of $8000-$FFFF,
+//so any write up there sets the whole bank register at once
+
+//mapper 2. switchable 16kb at $8000, last 16kb fixed at $C000, chr is (almost always) ram
+#[derive(Clone)]
+pub struct Uxrom {
+    prg_rom_size: usize,
+    chr_size: usize,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    prg_bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(header: &RomHeader) -> Result<Self, String> {
+        //the fixed bank at $C000 is the last 16kb, so there has to be at least that much
+        if header.prg_rom_size < 0x4000 {
+            return Err(format!(
+                "UxROM needs at least 16kb of prg rom, this one has {}",
+                header.prg_rom_size
+            ));
+        }
+        Ok(Uxrom {
+            prg_rom_size: header.prg_rom_size,
+            chr_size: header.chr_size(),
+            mirroring: header.mirroring,
+            //submapper 1 is the only variant guaranteed not to have them
+            bus_conflicts: header.submapper != 1,
+            prg_bank: 0,
+        })
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xBFFF => Some(
+                (self.prg_bank as usize * 0x4000 + (addr & 0x3FFF) as usize) % self.prg_rom_size,
+            ),
+            0xC000..=0xFFFF => Some(self.prg_rom_size - 0x4000 + (addr & 0x3FFF) as usize),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize> {
+        if addr >= 0x8000 {
+            self.prg_bank = byte;
+        }
+        None
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        (addr & 0x1FFF) as usize % self.chr_size
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some((addr & 0x1FFF) as usize % self.chr_size)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+    fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+//mapper 3. nrom with a switchable 8kb chr rom bank
+#[derive(Clone)]
+pub struct Cnrom {
+    prg_rom_size: usize,
+    chr_size: usize,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(header: &RomHeader) -> Self {
+        Cnrom {
+            prg_rom_size: header.prg_rom_size,
+            chr_size: header.chr_size(),
+            mirroring: header.mirroring,
+            bus_conflicts: header.submapper != 1,
+            chr_bank: 0,
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        (self.chr_bank as usize * 0x2000 + (addr & 0x1FFF) as usize) % self.chr_size
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xFFFF => Some((addr - 0x8000) as usize % self.prg_rom_size),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize> {
+        if addr >= 0x8000 {
+            self.chr_bank = byte;
+        }
+        None
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        self.chr_offset(addr)
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some(self.chr_offset(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+    fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+//mapper 7. switchable 32kb prg, and bit 4 picks which nametable the whole screen uses
+#[derive(Clone)]
+pub struct Axrom {
+    prg_rom_size: usize,
+    chr_size: usize,
+    bus_conflicts: bool,
+    //...M.PPP
+    reg: u8,
+}
+
+impl Axrom {
+    pub fn new(header: &RomHeader) -> Self {
+        Axrom {
+            prg_rom_size: header.prg_rom_size,
+            chr_size: header.chr_size(),
+            //only AMROM (submapper 2) has them, ANROM and AOROM gate the rom off
+            bus_conflicts: header.submapper == 2,
+            reg: 0,
+        }
+    }
+}
+
+impl Mapper for Axrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xFFFF => Some(
+                ((self.reg & 0b111) as usize * 0x8000 + (addr - 0x8000) as usize)
+                    % self.prg_rom_size,
+            ),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize> {
+        if addr >= 0x8000 {
+            self.reg = byte;
+        }
+        None
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        (addr & 0x1FFF) as usize % self.chr_size
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some((addr & 0x1FFF) as usize % self.chr_size)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if (self.reg & 0b0001_0000) != 0 {
+            Mirroring::SingleScreenB
+        } else {
+            Mirroring::SingleScreenA
+        }
+    }
+    fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+//mapper 66 (GxROM) and mapper 11 (Color Dreams) are the same idea, a 32kb prg bank and an
+//8kb chr bank in one register, they just put the bits in different places
+#[derive(Clone)]
+pub struct Gxrom {
+    prg_rom_size: usize,
+    chr_size: usize,
+    mirroring: Mirroring,
+    //..PP..CC
+    reg: u8,
+}
+
+impl Gxrom {
+    pub fn new(header: &RomHeader) -> Self {
+        Gxrom {
+            prg_rom_size: header.prg_rom_size,
+            chr_size: header.chr_size(),
+            mirroring: header.mirroring,
+            reg: 0,
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        ((self.reg & 0b11) as usize * 0x2000 + (addr & 0x1FFF) as usize) % self.chr_size
+    }
+}
+
+impl Mapper for Gxrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xFFFF => Some(
+                (((self.reg >> 4) & 0b11) as usize * 0x8000 + (addr - 0x8000) as usize)
+                    % self.prg_rom_size,
+            ),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize> {
+        if addr >= 0x8000 {
+            self.reg = byte;
+        }
+        None
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        self.chr_offset(addr)
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some(self.chr_offset(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+    fn bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone)]
+pub struct ColorDreams {
+    prg_rom_size: usize,
+    chr_size: usize,
+    mirroring: Mirroring,
+    //CCCC..PP
+    reg: u8,
+}
+
+impl ColorDreams {
+    pub fn new(header: &RomHeader) -> Self {
+        ColorDreams {
+            prg_rom_size: header.prg_rom_size,
+            chr_size: header.chr_size(),
+            mirroring: header.mirroring,
+            reg: 0,
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        ((self.reg >> 4) as usize * 0x2000 + (addr & 0x1FFF) as usize) % self.chr_size
+    }
+}
+
+impl Mapper for ColorDreams {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xFFFF => Some(
+                ((self.reg & 0b11) as usize * 0x8000 + (addr - 0x8000) as usize)
+                    % self.prg_rom_size,
+            ),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize> {
+        if addr >= 0x8000 {
+            self.reg = byte;
+        }
+        None
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        self.chr_offset(addr)
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some(self.chr_offset(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+    fn bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
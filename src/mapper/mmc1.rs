@@ -0,0 +1,190 @@
+use super::Mapper;
+use crate::cart::{Mirroring, RomHeader};
+
+//mapper 1, the MMC1 (SxROM boards)
+//the cpu talks to it one bit at a time: every write to $8000-$FFFF shifts bit 0 into a 5 bit
+//shift register, and the 5th write copies the result into whichever internal register
+//bits 13-14 of the address of that 5th write pointed to
+#[derive(Clone)]
+pub struct Mmc1 {
+    prg_rom_size: usize,
+    prg_ram_size: usize,
+    chr_size: usize,
+
+    //shift register. starts as 0b10000, the 1 falls out the bottom when its full
+    shift: u8,
+    //$8000-$9FFF: CPPMM (chr mode, prg mode, mirroring)
+    control: u8,
+    //$A000-$BFFF
+    chr_bank_0: u8,
+    //$C000-$DFFF
+    chr_bank_1: u8,
+    //$E000-$FFFF: RPPPP (prg ram disable, prg bank)
+    prg_bank: u8,
+
+    //the cpu cycle of the write being handled, and of the serial write before it.
+    //the MMC1 ignores a write on the cycle right after another one, so the dummy write
+    //of an rmw instruction (INC $8000 to reset, like Bill & Ted does) only counts once
+    cpu_cycle: u128,
+    last_write_cycle: Option<u128>,
+}
+
+const SHIFT_RESET: u8 = 0b1_0000;
+
+impl Mmc1 {
+    pub fn new(header: &RomHeader) -> Self {
+        Mmc1 {
+            prg_rom_size: header.prg_rom_size,
+            prg_ram_size: header.prg_ram_total(),
+            chr_size: header.chr_size(),
+            shift: SHIFT_RESET,
+            //power on in prg mode 3 so the reset vector is always in the last bank
+            control: 0b0_1100,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cpu_cycle: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    fn load_register(&mut self, addr: u16, byte: u8) {
+        let back_to_back = self.last_write_cycle == Some(self.cpu_cycle.wrapping_sub(1));
+        self.last_write_cycle = Some(self.cpu_cycle);
+        if back_to_back {
+            return;
+        }
+
+        //writing anything with bit 7 set resets the shift register and locks prg mode 3
+        if (byte & 0b1000_0000) != 0 {
+            self.shift = SHIFT_RESET;
+            self.control |= 0b0_1100;
+            return;
+        }
+
+        let full = (self.shift & 0x1) == 1;
+        self.shift = (self.shift >> 1) | ((byte & 0x1) << 4);
+
+        if full {
+            let val = self.shift;
+            match addr {
+                0x8000..=0x9FFF => self.control = val,
+                0xA000..=0xBFFF => self.chr_bank_0 = val,
+                0xC000..=0xDFFF => self.chr_bank_1 = val,
+                0xE000..=0xFFFF => self.prg_bank = val,
+                _ => unreachable!("MMC1 REGISTER WRITE OUTSIDE OF $8000-$FFFF"),
+            }
+            self.shift = SHIFT_RESET;
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_ram_size > 0 && (self.prg_bank & 0b1_0000) == 0
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        //SUROM (512kb) uses bit 4 of the chr bank register to pick which 256kb half we're in
+        let outer = if self.prg_rom_size > 0x40000 {
+            ((self.chr_bank_0 as usize >> 4) & 0x1) * 0x40000
+        } else {
+            0
+        };
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let last_bank = (self.prg_rom_size.min(0x40000) / 0x4000).saturating_sub(1);
+        let offset = (addr & 0x3FFF) as usize;
+
+        let final_bank = match (self.control >> 2) & 0b11 {
+            //switch all 32kb at once, ignoring the low bit of the bank number
+            0 | 1 => (bank & 0b1110) | ((addr as usize >> 14) & 0x1),
+            //first bank fixed at $8000, switch $C000
+            2 => {
+                if addr < 0xC000 {
+                    0
+                } else {
+                    bank
+                }
+            }
+            //switch $8000, last bank fixed at $C000
+            3 => {
+                if addr < 0xC000 {
+                    bank
+                } else {
+                    last_bank
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        (outer + final_bank * 0x4000 + offset) % self.prg_rom_size
+    }
+
+    fn prg_ram_offset(&self, addr: u16) -> usize {
+        //SOROM and SXROM reuse the chr bank lines to bank their bigger prg ram
+        let bank = match self.prg_ram_size {
+            0x4000 => (self.chr_bank_0 as usize >> 3) & 0x1,
+            0x8000 => (self.chr_bank_0 as usize >> 2) & 0b11,
+            _ => 0,
+        };
+        (bank * 0x2000 + (addr - 0x6000) as usize) % self.prg_ram_size
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let addr = (addr & 0x1FFF) as usize;
+        let offset = if (self.control & 0b1_0000) != 0 {
+            //two separate 4kb banks
+            if addr < 0x1000 {
+                self.chr_bank_0 as usize * 0x1000 + addr
+            } else {
+                self.chr_bank_1 as usize * 0x1000 + (addr - 0x1000)
+            }
+        } else {
+            //one 8kb bank, low bit ignored
+            (self.chr_bank_0 as usize & 0b1_1110) * 0x1000 + addr
+        };
+        offset % self.chr_size
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => Some(self.prg_ram_offset(addr)),
+            0x8000..=0xFFFF => Some(self.prg_rom_offset(addr)),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => Some(self.prg_ram_offset(addr)),
+            0x8000..=0xFFFF => {
+                self.load_register(addr, byte);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn write_cycle(&mut self, cpu_cycle: u128) {
+        self.cpu_cycle = cpu_cycle;
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        self.chr_offset(addr)
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some(self.chr_offset(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenA,
+            1 => Mirroring::SingleScreenB,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
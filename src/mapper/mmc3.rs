@@ -0,0 +1,201 @@
+use super::Mapper;
+use crate::cart::{Mirroring, RomHeader};
+
+//mapper 4, the MMC3 (TxROM boards)
+//8 bank registers selected through $8000/$8001, plus a scanline counter that gets clocked
+//every time ppu address line A12 goes from low to high after being low for a while.
+//with the usual setup (bg from $0000, sprites from $1000) that happens once per scanline
+#[derive(Clone)]
+pub struct Mmc3 {
+    prg_rom_size: usize,
+    prg_ram_size: usize,
+    chr_size: usize,
+    //four screen boards hardwire the mirroring and ignore $A000
+    four_screen: bool,
+
+    //$8000: CP...RRR (chr a12 inversion, prg mode, which register $8001 writes to)
+    bank_select: u8,
+    //R0-R7
+    banks: [u8; 8],
+    //$A000: 0 = vertical, 1 = horizontal
+    mirroring: u8,
+    //$A001: bit 7 enables the ram, bit 6 write protects it
+    prg_ram_protect: u8,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    //last value of A12 we saw on the ppu bus, and the ppu cycle it last went low
+    a12: bool,
+    a12_low_since: usize,
+}
+
+//A12 has to stay low for about 3 cpu cycles before a rise counts. this filters out the
+//short dips between sprite pattern fetches
+const A12_FILTER_DOTS: usize = 9;
+
+impl Mmc3 {
+    pub fn new(header: &RomHeader) -> Self {
+        Mmc3 {
+            prg_rom_size: header.prg_rom_size,
+            prg_ram_size: header.prg_ram_total(),
+            chr_size: header.chr_size(),
+            four_screen: header.mirroring == Mirroring::FourScreen,
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: 0,
+            prg_ram_protect: 0b1000_0000,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12: false,
+            a12_low_since: 0,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, byte: u8) {
+        let even = (addr & 0x1) == 0;
+        match (addr, even) {
+            (0x8000..=0x9FFF, true) => self.bank_select = byte,
+            (0x8000..=0x9FFF, false) => self.banks[(self.bank_select & 0b111) as usize] = byte,
+            (0xA000..=0xBFFF, true) => self.mirroring = byte & 0x1,
+            (0xA000..=0xBFFF, false) => self.prg_ram_protect = byte,
+            (0xC000..=0xDFFF, true) => self.irq_latch = byte,
+            //reload clears the counter, it gets refilled from the latch on the next clock
+            (0xC000..=0xDFFF, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            //disabling also acknowledges anything pending
+            (0xE000..=0xFFFF, true) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (0xE000..=0xFFFF, false) => self.irq_enabled = true,
+            _ => unreachable!("MMC3 REGISTER WRITE OUTSIDE OF $8000-$FFFF"),
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let bank_count = self.prg_rom_size / 0x2000;
+        let second_last = bank_count.saturating_sub(2);
+        let last = bank_count.saturating_sub(1);
+        let prg_mode = (self.bank_select & 0b0100_0000) != 0;
+
+        let bank = match (addr, prg_mode) {
+            (0x8000..=0x9FFF, false) => self.banks[6] as usize,
+            (0x8000..=0x9FFF, true) => second_last,
+            (0xA000..=0xBFFF, _) => self.banks[7] as usize,
+            (0xC000..=0xDFFF, false) => second_last,
+            (0xC000..=0xDFFF, true) => self.banks[6] as usize,
+            _ => last,
+        };
+
+        (bank * 0x2000 + (addr & 0x1FFF) as usize) % self.prg_rom_size
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        //chr a12 inversion swaps which half gets the 2kb banks and which gets the 1kb banks
+        let mut addr = addr & 0x1FFF;
+        if (self.bank_select & 0b1000_0000) != 0 {
+            addr ^= 0x1000;
+        }
+
+        let offset = match addr {
+            //2kb banks ignore the low bit
+            0x0000..=0x07FF => (self.banks[0] & 0xFE) as usize * 0x400 + addr as usize,
+            0x0800..=0x0FFF => (self.banks[1] & 0xFE) as usize * 0x400 + (addr - 0x0800) as usize,
+            _ => {
+                let reg = 2 + ((addr - 0x1000) / 0x400) as usize;
+                self.banks[reg] as usize * 0x400 + (addr & 0x3FF) as usize
+            }
+        };
+        offset % self.chr_size
+    }
+
+    fn prg_ram_readable(&self) -> bool {
+        self.prg_ram_size > 0 && (self.prg_ram_protect & 0b1000_0000) != 0
+    }
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_readable() && (self.prg_ram_protect & 0b0100_0000) == 0
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_readable() => {
+                Some((addr - 0x6000) as usize % self.prg_ram_size)
+            }
+            0x8000..=0xFFFF => Some(self.prg_rom_offset(addr)),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_writable() => {
+                Some((addr - 0x6000) as usize % self.prg_ram_size)
+            }
+            0x8000..=0xFFFF => {
+                self.write_register(addr, byte);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        self.chr_offset(addr)
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some(self.chr_offset(addr))
+    }
+
+    fn ppu_bus(&mut self, addr: u16, ppu_cycle: usize) {
+        let a12 = (addr & 0x1000) != 0;
+        if a12 && !self.a12 {
+            if ppu_cycle.wrapping_sub(self.a12_low_since) >= A12_FILTER_DOTS {
+                self.clock_irq_counter();
+            }
+        } else if !a12 && self.a12 {
+            self.a12_low_since = ppu_cycle;
+        }
+        self.a12 = a12;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.four_screen {
+            Mirroring::FourScreen
+        } else if self.mirroring == 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,79 @@
+use crate::cart::{Mirroring, RomHeader};
+
+mod discrete;
+mod mmc1;
+mod mmc3;
+mod nrom;
+
+pub use discrete::{Axrom, Cnrom, ColorDreams, Gxrom, Uxrom};
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use nrom::Nrom;
+
+//a cart is really just a cpu read/write mapping and a ppu read/write mapping, so thats all a mapper is.
+//the cart owns the actual prg/chr memory, mappers only translate bus addresses into offsets
+//into that memory (and soak up writes to their own registers along the way)
+pub trait Mapper: Send {
+    //$6000-$FFFF. returns an offset into prg ram for $6000-$7FFF and into prg rom for $8000-$FFFF,
+    //or None if nothing on the cart answers (open bus)
+    fn cpu_read(&mut self, addr: u16) -> Option<usize>;
+    //returns an offset if the write should land in cart memory, None if the mapper ate it
+    fn cpu_write(&mut self, addr: u16, byte: u8) -> Option<usize>;
+
+    //$0000-$1FFF. returns an offset into chr memory
+    fn ppu_read(&mut self, addr: u16) -> usize;
+    fn ppu_write(&mut self, addr: u16, byte: u8) -> Option<usize>;
+
+    //called with every address the ppu puts on its bus, for mappers that snoop on it
+    //(MMC3 counts scanlines by watching A12). ppu_cycle is the ppu's total dot count
+    fn ppu_bus(&mut self, _addr: u16, _ppu_cycle: usize) {}
+
+    //called right before cpu_write with the cpu cycle the write lands on,
+    //for mappers that care about write timing (MMC1 ignores back to back writes)
+    fn write_cycle(&mut self, _cpu_cycle: u128) {}
+
+    //which nametable layout the cart is currently asking for
+    fn mirroring(&self) -> Mirroring;
+
+    //true if the prg rom on this board keeps driving the data bus during writes, so
+    //register writes get ANDed with whatever rom byte lives at that address
+    fn bus_conflicts(&self) -> bool {
+        false
+    }
+
+    //true while the mapper is pulling the cpu's irq line low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    //we need this so that a Box<dyn Mapper> can be cloned along with the rest of the system
+    fn box_clone(&self) -> Box<dyn Mapper>;
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+//pick a mapper implementation based on the mapper number in the header
+pub fn new(header: &RomHeader) -> Result<Box<dyn Mapper>, String> {
+    //every mapper wraps its offsets around the rom size, so a rom with no prg at all cant work.
+    //(chr_size() is never 0, no chr rom means 8kb of chr ram)
+    if header.prg_rom_size == 0 {
+        return Err("rom has no prg rom".to_string());
+    }
+
+    let mapper: Box<dyn Mapper> = match header.mapper {
+        0 => Box::new(Nrom::new(header)),
+        1 => Box::new(Mmc1::new(header)),
+        2 => Box::new(Uxrom::new(header)?),
+        3 => Box::new(Cnrom::new(header)),
+        4 => Box::new(Mmc3::new(header)),
+        7 => Box::new(Axrom::new(header)),
+        11 => Box::new(ColorDreams::new(header)),
+        66 => Box::new(Gxrom::new(header)),
+        _ => return Err(format!("mapper {} is not supported yet", header.mapper)),
+    };
+    Ok(mapper)
+}
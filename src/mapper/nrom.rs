@@ -0,0 +1,61 @@
+use super::Mapper;
+use crate::cart::{Mirroring, RomHeader};
+
+//mapper 0. no bank switching at all
+//NROM-128 has 16kb of prg rom mirrored into both halves of $8000-$FFFF,
+//NROM-256 has 32kb filling the whole thing
+#[derive(Clone)]
+pub struct Nrom {
+    prg_rom_size: usize,
+    prg_ram_size: usize,
+    chr_size: usize,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(header: &RomHeader) -> Self {
+        Nrom {
+            prg_rom_size: header.prg_rom_size,
+            prg_ram_size: header.prg_ram_total(),
+            chr_size: header.chr_size(),
+            mirroring: header.mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<usize> {
+        match addr {
+            //family basic is the only nrom board with prg ram, but its harmless to allow it
+            0x6000..=0x7FFF if self.prg_ram_size > 0 => {
+                Some((addr - 0x6000) as usize % self.prg_ram_size)
+            }
+            0x8000..=0xFFFF => Some((addr - 0x8000) as usize % self.prg_rom_size),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_size > 0 => {
+                Some((addr - 0x6000) as usize % self.prg_ram_size)
+            }
+            //no registers to write to, writes to rom go nowhere
+            _ => None,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> usize {
+        (addr & 0x1FFF) as usize % self.chr_size
+    }
+    fn ppu_write(&mut self, addr: u16, _byte: u8) -> Option<usize> {
+        Some((addr & 0x1FFF) as usize % self.chr_size)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
@@ -1,9 +1,14 @@
-use crate::cpu::Cpu;
+use crate::apu::Apu;
+use crate::cpu::{Cpu, IRQ_DMC, IRQ_FRAME_COUNTER, IRQ_MAPPER};
 use crate::instr::Instr;
 use crate::ppu::Ppu;
 use crate::wram::Wram;
 
 use std::fmt::Write;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+//how often (in frames) battery backed prg ram gets written out, ~5 seconds
+const SAVE_INTERVAL_FRAMES: usize = 300;
 
 //TODO: remove this allow once we finish implementing all addressing modes
 #[allow(dead_code)]
@@ -31,11 +36,25 @@ pub struct NES {
     //pub cart: Cart,
     pub instr_data: Instr,
     pub wram: Wram,
-    //apu
+    pub apu: Apu,
     pub ppu: Ppu,
 
     //data about the system
     pub cycles: u128,
+    //page written to $4014, waiting for the current instruction to finish
+    pub dma_page: Option<u8>,
+    //an OAM DMA is copying right now, so a dmc fetch only costs 2 cycles
+    pub oam_dma_active: bool,
+    //cpu cycles the master clock has actually run. bus accesses tick it as they happen,
+    //and whatever the instruction spent internally gets ticked at the end to catch up to cycles
+    pub ticks: u128,
+    //ppu dots owed, in fifths of a dot. ntsc runs 3 dots per cpu cycle, pal 3.2
+    pub ppu_clock: u32,
+    pub pal: bool,
+    //run instructions a bus cycle at a time, dummy accesses and all (see cycle.rs)
+    pub cycle_stepped: bool,
+    //frame/halt messages from the ppu since the run loop last looked
+    pub ppu_messages: Vec<String>,
 
     //breakpoints halt execution when our PC equals that value
     pub breakpoints: Vec<usize>,
@@ -51,14 +70,84 @@ impl NES {
             cpu,
             //cart,
             wram,
+            apu: Apu::new(false),
             ppu,
             cycles: 7, //from intial reset vector
+            dma_page: None,
+            oam_dma_active: false,
+            ticks: 7,
+            ppu_clock: 0,
+            pal: false,
+            cycle_stepped: false,
+            ppu_messages: Vec::new(),
             instr_data: Instr::new(),
             breakpoints: Vec::new(),
             watchpoints: Vec::new(),
         }
     }
 
+    //power on state (https://www.nesdev.org/wiki/CPU_power_up_state). A/X/Y are 0, P has I set,
+    //and SP starts at 0 so the reset sequence leaves it at $FD. ram is actually random on
+    //hardware, but zeroes is what everyone expects
+    pub fn power_on(&mut self) {
+        self.cpu = Cpu::new();
+        self.cpu.SP = 0x00;
+        self.wram = Wram::new();
+        //whatever the apu was playing into stays hooked up
+        let output = self.apu.output.take();
+        let channel_outputs = self.apu.channel_outputs.take();
+        self.apu = Apu::new(self.pal);
+        self.apu.output = output;
+        self.apu.channel_outputs = channel_outputs;
+        self.ppu.pal = self.pal;
+        self.ppu.power_on();
+        self.dma_page = None;
+        self.cycles = 0;
+        self.ticks = 0;
+        self.reset_sequence();
+    }
+
+    //the reset button. registers and ram keep whatever they had, the ppu clears
+    //PPUCTRL/PPUMASK, the apu goes quiet, and the cpu runs the reset sequence again (so SP drops by another 3)
+    pub fn reset(&mut self) {
+        self.ppu.reset();
+        self.apu.reset();
+        self.dma_page = None;
+        self.reset_sequence();
+    }
+
+    //reset is an interrupt where the pushes turn into reads, so SP moves by 3 but nothing gets written.
+    //then I gets set and we jump through $FFFC, 7 cycles total
+    fn reset_sequence(&mut self) {
+        self.cpu.SP = self.cpu.SP.wrapping_sub(3);
+        self.cpu.SR.I = true;
+        self.cpu.irq_inhibit = true;
+        let target = self.read(0xFFFC, 2);
+        self.cpu.PC = target[0] as u16 | (target[1] as u16) << 8;
+        self.cycles += 7;
+        self.catch_up();
+    }
+
+    //one cpu cycle on the master clock. everything else advances in lockstep with it
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        self.apu.tick();
+        self.ppu_clock += if self.pal { 16 } else { 15 };
+        while self.ppu_clock >= 5 {
+            self.ppu_clock -= 5;
+            if let Err(msg) = self.ppu.step() {
+                self.ppu_messages.push(msg);
+            }
+        }
+    }
+
+    //tick through the cycles an instruction spent without touching the bus
+    fn catch_up(&mut self) {
+        while self.ticks < self.cycles {
+            self.tick();
+        }
+    }
+
     pub fn add_watchpoint(&mut self, addr: usize) {
         self.watchpoints.push(addr);
     }
@@ -67,7 +156,8 @@ impl NES {
     }
 
     //function to run this system in its own thread, takes a SENDER channel to return logs on to the rendering thread
-    pub fn run(&mut self, mut good_log: Vec<String>) {
+    //quit fires (or hangs up) when the window closes, so we get a chance to flush the save file
+    pub fn run(&mut self, mut good_log: Vec<String>, quit: Receiver<()>) {
         /*let file = OpenOptions::new()
         .write(true)
         .truncate(true)
@@ -78,13 +168,20 @@ impl NES {
 
         let mut halt = false;
         let mut pending_logs: Vec<String> = Vec::new();
+        let mut last_save = self.ppu.frames;
         //endless running loop
         loop {
+            if !matches!(quit.try_recv(), Err(TryRecvError::Empty)) {
+                if let Err(e) = self.ppu.cart.save() {
+                    eprintln!("{e}");
+                }
+                return;
+            }
+
             /*let good_line = match good_log.pop() {
                 Some(v) => v,
                 None => panic!("log file is empty???"),
             };*/
-            let cur_instr = self.read(self.cpu.PC, 1)[0];
             match self.step() {
                 //Ok means that we didnt encounter anything out of the ordinary in our step
                 Ok(our_line) => {
@@ -115,20 +212,17 @@ impl NES {
                     halt = true;
                 }
             }
-            //one cpu tick
-            //self.step().unwrap();
-            //three ppu ticks
-            //pending_logs.push(self.ppu.step().unwrap());
-            //pending_logs.push(self.ppu.step().unwrap());
-            //pending_logs.push(self.ppu.step().unwrap());
-            //TODO: this was a stupid fix in the first place and you know it lmfao
-            for _i in 0..self.instr_data.instrs.get(&cur_instr).unwrap().cycles {
-                match self.ppu.step() {
-                    Ok(log) => pending_logs.push(log),
-                    Err(log) => {
-                        pending_logs.push(log);
-                        halt = true;
-                    }
+            //the ppu runs inside the step now (see tick), anything it wanted to tell us is queued up
+            for msg in self.ppu_messages.drain(..) {
+                pending_logs.push(msg);
+                halt = true;
+            }
+            //flush battery ram every few seconds too, so a crash doesnt eat the save
+            if self.ppu.frames >= last_save + SAVE_INTERVAL_FRAMES {
+                last_save = self.ppu.frames;
+                if let Err(e) = self.ppu.cart.save() {
+                    pending_logs.push(e);
+                    halt = true;
                 }
             }
 
@@ -197,11 +291,13 @@ impl NES {
                 let mut zpg_addr: u16 = bytes[1] as u16;
                 //effective address is word in (LL + X, LL + X + 1), inc. without carry: C.w($00LL + X)
                 zpg_addr = zpg_addr.wrapping_add(self.cpu.X as u16) & 0xFF;
-                //we read from this zeropage address to get our effective address
-                let ea_l = self.read(zpg_addr, 1)[0];
+                //we read from this zeropage address to get our effective address.
+                //calc_addr gets called more than once per instruction, so the pointer reads are peeks
+                //and their cycles get picked up by the catch up at the end of the step
+                let ea_l = self.peek(zpg_addr, 1)[0];
                 //make sure we mask to keep this as a zpg addr
                 zpg_addr = zpg_addr.wrapping_add(1) & 0xFF;
-                let ea_h = self.read(zpg_addr, 1)[0];
+                let ea_h = self.peek(zpg_addr, 1)[0];
                 let addr: u16 = ea_l as u16 | (ea_h as u16) << 8;
                 //returning the effective address
                 addr
@@ -211,9 +307,9 @@ impl NES {
                 //operand is zeropage address; effective address is word in (LL, LL + 1) incremented by Y with carry:
                 //C.w($00LL) + Y
                 let mut zpg_addr: u16 = bytes[1] as u16;
-                let ea_l = self.read(zpg_addr, 1)[0];
+                let ea_l = self.peek(zpg_addr, 1)[0];
                 zpg_addr = zpg_addr.wrapping_add(1) & 0xFF;
-                let ea_h = self.read(zpg_addr, 1)[0];
+                let ea_h = self.peek(zpg_addr, 1)[0];
                 let base_addr: u16 = ea_l as u16 | (ea_h as u16) << 8;
                 let addr = base_addr.wrapping_add(self.cpu.Y as u16);
 
@@ -278,6 +374,32 @@ impl NES {
         mode: AddrMode,
         stepstring: &mut String,
         penalty: bool,
+    ) -> u8 {
+        self.operand(bytes, mode, stepstring, penalty, false)
+    }
+
+    //same log output as get_val, but only peeks at the bus. for stores and read-modify-writes,
+    //which do their own real accesses and just want the old value in the log
+    pub fn log_val(&mut self, bytes: &[u8], mode: AddrMode, stepstring: &mut String) {
+        self.operand(bytes, mode, stepstring, false, true);
+    }
+
+    //a real bus read, or a side effect free one if we only want it for the log
+    fn fetch(&mut self, addr: u16, peek: bool) -> u8 {
+        if peek {
+            self.peek(addr, 1)[0]
+        } else {
+            self.read(addr, 1)[0]
+        }
+    }
+
+    fn operand(
+        &mut self,
+        bytes: &[u8],
+        mode: AddrMode,
+        stepstring: &mut String,
+        penalty: bool,
+        peek: bool,
     ) -> u8 {
         //NOTE: this is split out as a match case because we need to print different stuff based on
         // addr mode, otherwise we could just always calc addr and read a byte
@@ -288,13 +410,13 @@ impl NES {
             }
             AddrMode::ABS => {
                 let addr = self.calc_addr(bytes, AddrMode::ABS, penalty);
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
                 write!(stepstring, "${:04X} = {:02X}", addr, val).unwrap();
                 val
             }
             AddrMode::ABSX => {
                 let addr = self.calc_addr(bytes, AddrMode::ABSX, penalty);
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
 
                 write!(
                     stepstring,
@@ -307,7 +429,7 @@ impl NES {
             }
             AddrMode::ABSY => {
                 let addr = self.calc_addr(bytes, AddrMode::ABSY, penalty);
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
 
                 //$0300,Y @ 0300 = 89
                 write!(
@@ -325,13 +447,13 @@ impl NES {
             }
             AddrMode::IND => {
                 let addr = self.calc_addr(bytes, AddrMode::IND, penalty);
-                self.read(addr, 1)[0]
+                self.fetch(addr, peek)
             }
             AddrMode::INDX => {
                 //this is our effective(final) address
                 let addr = self.calc_addr(bytes, AddrMode::INDX, penalty);
                 //we read from this address to get our value
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
 
                 //bytes , bytes+x, ea, final val
                 //($80,X) @ 80 = 0200 = 5A
@@ -349,7 +471,7 @@ impl NES {
             }
             AddrMode::INDY => {
                 let addr = self.calc_addr(bytes, AddrMode::INDY, penalty);
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
 
                 // bytes, ea,    +y, val
                 //LDA ($89),Y = 0300 @ 0300 = 89
@@ -357,8 +479,8 @@ impl NES {
                     stepstring,
                     "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
                     bytes[1],
-                    self.read(bytes[1] as u16, 1)[0] as u16
-                        | (self.read((bytes[1] as u16).wrapping_add(1) & 0xFF, 1)[0] as u16) << 8,
+                    self.peek(bytes[1] as u16, 1)[0] as u16
+                        | (self.peek((bytes[1] as u16).wrapping_add(1) & 0xFF, 1)[0] as u16) << 8,
                     addr,
                     val
                 )
@@ -368,17 +490,17 @@ impl NES {
             }
             AddrMode::REL => {
                 let addr = self.calc_addr(bytes, AddrMode::REL, penalty);
-                self.read(addr, 1)[0]
+                self.fetch(addr, peek)
             }
             AddrMode::ZPG => {
                 let addr = self.calc_addr(bytes, AddrMode::ZPG, penalty);
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
                 write!(stepstring, "${:02X} = {:02X}", addr as u8, val).unwrap();
                 val
             }
             AddrMode::ZPGX => {
                 let addr = self.calc_addr(bytes, AddrMode::ZPGX, penalty);
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
                 write!(
                     stepstring,
                     "${:02X},X @ {:02X} = {:02X}",
@@ -390,7 +512,7 @@ impl NES {
             }
             AddrMode::ZPGY => {
                 let addr = self.calc_addr(bytes, AddrMode::ZPGY, penalty);
-                let val = self.read(addr, 1)[0];
+                let val = self.fetch(addr, peek);
                 write!(
                     stepstring,
                     "${:02X},Y @ {:02X} = {:02X}",
@@ -454,6 +576,67 @@ impl NES {
         }
     }
 
+    //interrupt sequence, shared by BRK, IRQ and NMI. pushes PC and the status register
+    //(B set only for BRK), sets I, and jumps through the given vector. takes 7 cycles
+    pub fn interrupt(&mut self, vector: u16, brk: bool) {
+        let pc = self.cpu.PC;
+        self.cpu.push(&mut self.wram, (pc >> 8) as u8);
+        self.cpu.push(&mut self.wram, (pc & 0xFF) as u8);
+
+        let mut saved_sr = self.cpu.SR;
+        saved_sr.BH = true;
+        saved_sr.BL = brk;
+        self.cpu.push(&mut self.wram, saved_sr.decode());
+        self.cpu.SR.I = true;
+        self.cpu.irq_inhibit = true;
+
+        //2 dummy reads (or the opcode and padding byte for BRK) and the 3 pushes come before the
+        //vector fetch. they need to happen on the clock so an nmi has a chance to show up
+        while self.ticks < self.cycles + 5 {
+            self.tick();
+        }
+
+        //an nmi that comes in before the vector fetch hijacks a BRK/IRQ sequence. whatever
+        //already got pushed stays pushed (B included), we just end up in the nmi handler
+        let vector = if vector == 0xFFFE && self.ppu.nmi_pending {
+            self.ppu.nmi_pending = false;
+            0xFFFA
+        } else {
+            vector
+        };
+
+        let target = self.read(vector, 2);
+        self.cpu.PC = target[0] as u16 | (target[1] as u16) << 8;
+        self.cycles += 7;
+    }
+
+    //copies $XX00-$XXFF into OAM through OAMDATA. takes 1 cycle to halt the cpu, another
+    //if we land on an odd cycle so the reads/writes line up, then 256 read/write pairs.
+    //returns how many cycles the cpu was stalled for
+    pub fn oam_dma(&mut self, page: u8) -> u128 {
+        //the instruction that wrote $4014 finishes first
+        self.catch_up();
+        let alignment = if !self.cycles.is_multiple_of(2) { 2 } else { 1 };
+        for _ in 0..alignment {
+            self.tick();
+        }
+
+        let base = (page as u16) << 8;
+        self.oam_dma_active = true;
+        for i in 0..256 {
+            //get cycle
+            let byte = self.read(base + i, 1)[0];
+            //put cycle
+            self.tick();
+            self.ppu.reg_write(0x4, byte);
+        }
+        self.oam_dma_active = false;
+
+        let stall = alignment + 512;
+        self.cycles += stall;
+        stall
+    }
+
     //stepping our system can either return an Ok(log string) or an Err(step_error)
     pub fn step(&mut self) -> Result<String, String> {
         //if we are at a breakpoint, take no action, and set our running flag to false
@@ -462,6 +645,60 @@ impl NES {
             return Err(format!("Hit breakpoint at PC = {:04X}", self.cpu.PC));
         }
 
+        let result = match self.poll_interrupts() {
+            Some(line) => Ok(line),
+            None if self.cycle_stepped => self.step_cycles(),
+            None => self.step_instr(),
+        };
+        self.catch_up();
+        result
+    }
+
+    //runs an nmi or irq sequence if one is waiting, and returns its log line
+    fn poll_interrupts(&mut self) -> Option<String> {
+        //nmi beats irq. its edge triggered, so the ppu has already latched it for us
+        if self.ppu.nmi_pending {
+            self.ppu.nmi_pending = false;
+            let from = self.cpu.PC;
+            self.interrupt(0xFFFA, false);
+            return Some(format!(
+                "{from:04X}  NMI -> ${:04X}  {} CYC:{}",
+                self.cpu.PC, self.cpu, self.cycles
+            ));
+        }
+
+        //devices assert irq whenever they like, but the cpu only looks at instruction boundaries.
+        //its level triggered, so a device has to keep holding it until the handler acknowledges it
+        self.cpu.set_irq(IRQ_MAPPER, self.ppu.cart.irq());
+        self.cpu.set_irq(IRQ_FRAME_COUNTER, self.apu.frame_irq());
+        self.cpu.set_irq(IRQ_DMC, self.apu.dmc_irq());
+        if self.cpu.irq_line() && !self.cpu.irq_inhibit {
+            let from = self.cpu.PC;
+            self.interrupt(0xFFFE, false);
+            return Some(format!(
+                "{from:04X}  IRQ -> ${:04X}  {} CYC:{}",
+                self.cpu.PC, self.cpu, self.cycles
+            ));
+        }
+
+        None
+    }
+
+    //bookkeeping after every instruction, whichever core ran it. returns the dma stall if there was one
+    pub fn end_of_instr(&mut self, instr: u8, i_before: bool) -> Option<u128> {
+        //CLI, SEI and PLP poll for interrupts before they change I
+        self.cpu.irq_inhibit = if matches!(instr, 0x58 | 0x78 | 0x28) {
+            i_before
+        } else {
+            self.cpu.SR.I
+        };
+
+        //the cpu gets halted for the whole OAM DMA, right after the instruction that started it
+        self.dma_page.take().map(|page| self.oam_dma(page))
+    }
+
+    //runs one instruction. bus accesses tick the clock as they go
+    fn step_instr(&mut self) -> Result<String, String> {
         //for debugging, lets build a string to output this step
         let mut stepstring = String::new();
 
@@ -469,7 +706,8 @@ impl NES {
         // our current addr,
         // the bytes that make up this instr,
         // padding out to 16 chars
-        let instr: u8 = self.read(self.cpu.PC, 1)[0];
+        //peek at the opcode first, the real fetch happens along with the operands below
+        let instr: u8 = self.peek(self.cpu.PC, 1)[0];
 
         //DEBUG
         match self.instr_data.instrs.get(&instr) {
@@ -494,6 +732,8 @@ impl NES {
         )
         .unwrap();
 
+        let i_before = self.cpu.SR.I;
+
         //simulates full opcode space, including illegal instructions
         match instr {
             /*
@@ -1002,6 +1242,8 @@ impl NES {
                 panic!("unimplemented op {:#02x}", instr)
             }
         }
+        let dma_stall = self.end_of_instr(instr, i_before);
+
         //print padding and then cpu state and cycles
         let final_padding = vec![" "; 48 - stepstring.len()].join("");
         write!(
@@ -1010,6 +1252,9 @@ impl NES {
             self.cpu, self.cycles
         )
         .unwrap();
+        if let Some(stall) = dma_stall {
+            write!(stepstring, " OAMDMA:{stall}").unwrap();
+        }
 
         Ok(stepstring)
     }
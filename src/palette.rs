@@ -0,0 +1,149 @@
+use std::fs;
+
+//the 64 colors the 2C02 can output, indexed by the 6 bit values stored in palette ram.
+//these are the usual "2C02" rgb approximations. the ppu doesnt actually output rgb,
+//it generates an ntsc signal directly, so every palette is a guess to some degree
+pub const NES_PALETTE: [[u8; 3]; 64] = [
+    //$00-$0F
+    [84, 84, 84],
+    [0, 30, 116],
+    [8, 16, 144],
+    [48, 0, 136],
+    [68, 0, 100],
+    [92, 0, 48],
+    [84, 4, 0],
+    [60, 24, 0],
+    [32, 42, 0],
+    [8, 58, 0],
+    [0, 64, 0],
+    [0, 60, 0],
+    [0, 50, 60],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+    //$10-$1F
+    [152, 150, 152],
+    [8, 76, 196],
+    [48, 50, 236],
+    [92, 30, 228],
+    [136, 20, 176],
+    [160, 20, 100],
+    [152, 34, 32],
+    [120, 60, 0],
+    [84, 90, 0],
+    [40, 114, 0],
+    [8, 124, 0],
+    [0, 118, 40],
+    [0, 102, 120],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+    //$20-$2F
+    [236, 238, 236],
+    [76, 154, 236],
+    [120, 124, 236],
+    [176, 98, 236],
+    [228, 84, 236],
+    [236, 88, 180],
+    [236, 106, 100],
+    [212, 136, 32],
+    [160, 170, 0],
+    [116, 196, 0],
+    [76, 208, 32],
+    [56, 204, 108],
+    [56, 180, 204],
+    [60, 60, 60],
+    [0, 0, 0],
+    [0, 0, 0],
+    //$30-$3F
+    [236, 238, 236],
+    [168, 204, 236],
+    [188, 188, 236],
+    [212, 178, 236],
+    [236, 174, 236],
+    [236, 174, 212],
+    [236, 180, 176],
+    [228, 196, 144],
+    [204, 210, 120],
+    [180, 222, 120],
+    [168, 226, 144],
+    [152, 226, 180],
+    [160, 214, 228],
+    [160, 162, 160],
+    [0, 0, 0],
+    [0, 0, 0],
+];
+
+//how much a channel gets dimmed when one of the *other* channels is emphasized
+const EMPHASIS_ATTENUATION: f32 = 0.816;
+
+//the full set of colors the ppu can output: 64 colors x 8 combinations of the
+//red/green/blue emphasis bits in PPUMASK
+#[derive(Clone)]
+pub struct MasterPalette {
+    //index is emphasis * 64 + color
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl MasterPalette {
+    //our built in palette, with the emphasis variants worked out from it
+    pub fn new() -> Self {
+        MasterPalette {
+            colors: with_emphasis(&NES_PALETTE),
+        }
+    }
+
+    //loads a .pal file. 192 bytes is just the 64 base colors, 1536 bytes is
+    //all 8 emphasis combinations already baked in by whoever made the palette
+    pub fn from_file(filename: &str) -> Result<Self, String> {
+        let raw = fs::read(filename).map_err(|e| format!("couldnt read {filename}: {e}"))?;
+        MasterPalette::from_bytes(&raw)
+    }
+
+    pub fn from_bytes(raw: &[u8]) -> Result<Self, String> {
+        let rgb: Vec<[u8; 3]> = raw.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+        match raw.len() {
+            192 => {
+                let mut base = [[0; 3]; 64];
+                base.copy_from_slice(&rgb);
+                Ok(MasterPalette {
+                    colors: with_emphasis(&base),
+                })
+            }
+            1536 => Ok(MasterPalette { colors: rgb }),
+            n => Err(format!(
+                "palette files should be 192 or 1536 bytes, this one is {n}"
+            )),
+        }
+    }
+
+    //color is the 6 bit value out of palette ram, emphasis is PPUMASK bits 5-7 (BGR)
+    pub fn rgb(&self, color: u8, emphasis: u8) -> [u8; 3] {
+        self.colors[(emphasis as usize & 0b111) * 64 + (color as usize & 0x3F)]
+    }
+}
+
+//each emphasis bit dims the two channels it isnt named after. the blacks in
+//columns $xE/$xF are outside the color generator so emphasis doesnt touch them
+fn with_emphasis(base: &[[u8; 3]; 64]) -> Vec<[u8; 3]> {
+    let mut colors = Vec::with_capacity(512);
+    for emphasis in 0..8 {
+        for (i, color) in base.iter().enumerate() {
+            let mut scaled = [color[0] as f32, color[1] as f32, color[2] as f32];
+            if (i & 0x0F) < 0x0E {
+                //bit 0 is red, bit 1 is green, bit 2 is blue
+                for channel in 0..3 {
+                    if (emphasis >> channel) & 0x1 == 1 {
+                        for (other, value) in scaled.iter_mut().enumerate() {
+                            if other != channel {
+                                *value *= EMPHASIS_ATTENUATION;
+                            }
+                        }
+                    }
+                }
+            }
+            colors.push([scaled[0] as u8, scaled[1] as u8, scaled[2] as u8]);
+        }
+    }
+    colors
+}
@@ -1,6 +1,6 @@
 use crate::cart::Cart;
+use crate::palette::MasterPalette;
 use crate::vram::Vram;
-use std::fmt::Write;
 use std::sync::mpsc::Sender;
 
 #[allow(dead_code)]
@@ -11,11 +11,62 @@ use std::sync::mpsc::Sender;
 pub struct Ppu {
     //256B OAM
     pub OAM: [u8; 256],
-    //OAM DMA? - this *technically* goes through the ppuregs via OAMADDR and OAMDMA
-    //Palette??
+    //OAM DMA goes through OAMDATA, so it starts wherever OAMADDR is pointing
+    //32B of palette ram, $3F00-$3F1F (mirrored up to $3FFF)
+    //$3F00 is the universal backdrop, then 4 background palettes and 4 sprite palettes
+    pub palette: [u8; 32],
+    //palette ram values -> actual rgb colors
+    pub master_palette: MasterPalette,
     //Control Registers - THESE ARE MEMORY MAPPED IN CPU'S MEM SPACE
     pub regs: PPUREGS,
 
+    //internal scroll/address registers (loopy's names)
+    //v: current vram address (15 bits), t: temporary vram address / top left of the screen
+    //x: fine x scroll (3 bits), w: first or second write toggle for PPUSCROLL and PPUADDR
+    pub v: u16,
+    pub t: u16,
+    pub x: u8,
+    pub w: bool,
+    //PPUDATA reads come out of this buffer, and the read refills it
+    pub read_buffer: u8,
+    //nmi edge detection. the ppu pulls /NMI low while VBLANK and nmi_enable are both set,
+    //and the cpu latches the falling edge until it gets to an instruction boundary
+    pub nmi_previous: bool,
+    pub nmi_pending: bool,
+    //PPUSTATUS got read right before vblank started, so the flag never goes up this frame
+    pub suppress_vblank: bool,
+    //after power/reset the ppu ignores PPUCTRL, PPUMASK, PPUSCROLL and PPUADDR writes
+    //until the end of the first vblank
+    pub warming_up: bool,
+    //the cpu<->ppu data bus holds on to the last value written to any register.
+    //reads of write only registers (and the low bits of PPUSTATUS) return it
+    pub io_latch: u8,
+
+    //background pipeline. the next tile's fetched bytes wait in the latches
+    //until they're loaded into the low half of the 16 bit shift registers
+    pub bg_next_tile: u8,
+    pub bg_next_attrib: u8,
+    pub bg_next_lo: u8,
+    pub bg_next_hi: u8,
+    pub bg_shift_pattern_lo: u16,
+    pub bg_shift_pattern_hi: u16,
+    pub bg_shift_attrib_lo: u16,
+    pub bg_shift_attrib_hi: u16,
+
+    //sprites. every line the ppu picks (up to) 8 sprites out of OAM for the next line
+    //and copies them into secondary OAM, then fetches their patterns on dots 257-320
+    pub secondary_oam: [u8; 32],
+    pub secondary_count: usize,
+    //sprite 0 made it into secondary OAM
+    pub sprite_zero_next: bool,
+    //what we fetched for the line we're drawing right now
+    pub sprite_count: usize,
+    pub sprite_zero_line: bool,
+    pub sprite_lo: [u8; 8],
+    pub sprite_hi: [u8; 8],
+    pub sprite_attrib: [u8; 8],
+    pub sprite_x: [u8; 8],
+
     pub vram: Vram,
     pub cart: Cart,
 
@@ -28,6 +79,9 @@ pub struct Ppu {
 
     //number of frames from boot
     pub frames: usize,
+    //pal ppus run 312 lines a frame instead of 262. the extra 50 all go to vblank,
+    //so the pre-render line moves down to 311
+    pub pal: bool,
     //mpsc channel for sending a frame to the app thread
     pub channel: Sender<Vec<u8>>,
     pub frame: Vec<u8>,
@@ -37,13 +91,43 @@ impl Ppu {
     pub fn new(cart: Cart, channel: Sender<Vec<u8>>) -> Self {
         Ppu {
             OAM: [0; 256],
+            palette: [0; 32],
+            master_palette: MasterPalette::new(),
             regs: PPUREGS::new(),
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            read_buffer: 0,
+            io_latch: 0,
+            nmi_previous: false,
+            nmi_pending: false,
+            suppress_vblank: false,
+            warming_up: false,
+            bg_next_tile: 0,
+            bg_next_attrib: 0,
+            bg_next_lo: 0,
+            bg_next_hi: 0,
+            bg_shift_pattern_lo: 0,
+            bg_shift_pattern_hi: 0,
+            bg_shift_attrib_lo: 0,
+            bg_shift_attrib_hi: 0,
+            secondary_oam: [0xFF; 32],
+            secondary_count: 0,
+            sprite_zero_next: false,
+            sprite_count: 0,
+            sprite_zero_line: false,
+            sprite_lo: [0; 8],
+            sprite_hi: [0; 8],
+            sprite_attrib: [0; 8],
+            sprite_x: [0; 8],
             vram: Vram::new(),
             cart,
             cur_dot: 0,
             cur_line: 0,
             cycles: 0,
             frames: 0,
+            pal: false,
             channel,
             frame: vec![200; 184_320],
         }
@@ -61,14 +145,9 @@ pub struct PPUREGS {
     pub PPUSTATUS: PPUSTATUS,
     //OAMADDR: 0x2003
     pub OAMADDR: u8,
-    //OAMDATA: 0x2004
-    pub OAMDATA: u8,
-    //PPUSCROLL: 0x2005
-    pub PPUSCROLL: u8,
-    //PPUADDR: 0x2006
-    pub PPUADDR: u8,
-    //PPUDATA: 0x2007
-    pub PPUDATA: u8,
+    //OAMDATA (0x2004) goes straight into OAM at OAMADDR
+    //PPUSCROLL (0x2005), PPUADDR (0x2006) and PPUDATA (0x2007) dont hold anything themselves,
+    //they all go through v/t/x/w on the ppu
     //OAMDMA: 0x4014
     pub OAMDMA: u8,
 }
@@ -79,10 +158,6 @@ impl PPUREGS {
             PPUMASK: 0.into(),
             PPUSTATUS: 0.into(),
             OAMADDR: 0,
-            OAMDATA: 0,
-            PPUSCROLL: 0,
-            PPUADDR: 0,
-            PPUDATA: 0,
             OAMDMA: 0,
         }
     }
@@ -241,176 +316,659 @@ impl Into<u8> for PPUSTATUS {
 }
 
 impl Ppu {
-    //tick our ppu one CLOCK CYCLE
-    //TODO: this is our remnants of a per-pixel renderer
-    /*
-    pub fn step(&mut self) {
+    //tick our ppu one CLOCK CYCLE (one dot)
+    pub fn step(&mut self) -> Result<String, String> {
+        let rendering = self.rendering();
+        let pre_render = self.pre_render_line();
         match self.cur_line {
-            //visible lines
-            0..=239 => {
-                match self.cur_dot {
-                    //idle cycle
-                    0 => {
-                        //we literally do nothing on this cycle
-                    }
-                    //data fetch AND next line sprite eval
-                    1..=256 => {
-                        let phase = (self.cur_dot - 1) % 8;
-                        match phase {
-                            //1.Nametable byte
-                            0 => {
-                                //fetch from $2000
-                                self.read(0x2000, 1);
-                            }
-                            1 => {
-                            }
-                            //2.Attribute table byte
-                            2 => {}
-                            3 => {}
-                            //3.Pattern table tile low
-                            4 => {}
-                            5 => {}
-                            //4.Pattern table tile high (+8 bytes from pattern table tile low)
-                            6 => {}
-                            7 => {}
-                            _ => unreachable!("we are in a visible line data fetch phase, but modded an invalid value")
-                        }
+            //visible lines + the pre-render line, which does all the same fetches without drawing
+            line if line < 240 || line == pre_render => {
+                if self.cur_line == pre_render && self.cur_dot == 1 {
+                    self.warming_up = false;
+                    self.regs.PPUSTATUS.VBLANK = false;
+                    self.regs.PPUSTATUS.SPRITE_0_HIT = false;
+                    self.regs.PPUSTATUS.SPRITE_OVERFLOW = false;
+                    self.update_nmi();
+                }
+
+                if rendering {
+                    self.background_dot();
+                    self.sprite_dot();
+                }
+
+                if self.cur_line < 240 && (1..=256).contains(&self.cur_dot) {
+                    self.draw_pixel();
+                }
+            }
+            //post-render scanline
+            240 => {
+                //literally do nothing. safe to access ppu memory, but no vblank flag has been raised
+            }
+            //vblanking, 241-260 (241-310 on pal)
+            line if line < pre_render => {
+                if self.cur_line == 241 && self.cur_dot == 1 {
+                    if !self.suppress_vblank {
+                        self.regs.PPUSTATUS.VBLANK = true;
+                        self.update_nmi();
                     }
-                    //NEXT LINE sprite fetch
-                    257..=320 => {}
-                    //NEXT LINE first two tiles
-                    321..=336 => {
-                        //send the frame data on the last dot of the last visible line
-                        if self.cur_line == 239 && self.cur_dot == 336 {
-                            self.channel.send(self.frame.clone()).unwrap();
-                            self.frames += 1;
-                        }
+                    self.suppress_vblank = false;
+                }
+            }
+            _ => unreachable!("IN A SCANLINE THAT DOESNT EXIST"),
+        }
+
+        self.tick_beam();
+
+        //we just wrapped back around to the top, so the frame is done
+        if self.cur_line == 0 && self.cur_dot == 0 {
+            //the window might be gone (or never existed, when running headless)
+            let _ = self.channel.send(self.frame.clone());
+            self.frames += 1;
+            return Err("just sent a frame".to_string());
+        }
+
+        Ok(String::new())
+    }
+
+    //the last line of the frame, 261 on ntsc and 311 on pal
+    pub fn pre_render_line(&self) -> usize {
+        if self.pal {
+            311
+        } else {
+            261
+        }
+    }
+
+    //the visible lines and the pre-render line, where the ppu is busy fetching and OAM is in use
+    fn render_line(&self) -> bool {
+        self.cur_line < 240 || self.cur_line == self.pre_render_line()
+    }
+
+    //rendering is on if either background or sprites are enabled
+    pub fn rendering(&self) -> bool {
+        self.regs.PPUMASK.BACKGROUND_EABLE || self.regs.PPUMASK.SPRITE_ENABLE
+    }
+
+    /*background fetches, every 8 dots on dots 1-256 and 321-336:
+    dot 1-2 nametable byte, 3-4 attribute byte, 5-6 pattern lo, 7-8 pattern hi (+8 bytes)
+    the shift registers move one bit per dot and get the next tile loaded in every 8 dots*/
+    fn background_dot(&mut self) {
+        let dot = self.cur_dot;
+
+        if matches!(dot, 2..=257 | 322..=337) {
+            self.bg_shift_pattern_lo <<= 1;
+            self.bg_shift_pattern_hi <<= 1;
+            self.bg_shift_attrib_lo <<= 1;
+            self.bg_shift_attrib_hi <<= 1;
+        }
+
+        if matches!(dot, 1..=256 | 321..=336) {
+            match (dot - 1) % 8 {
+                0 => {
+                    self.load_background_shifters();
+                    self.bg_next_tile = self.read(0x2000 | (self.v & 0x0FFF), 1)[0];
+                }
+                2 => {
+                    //each attribute byte covers a 4x4 tile area, 2 bits per 2x2 quadrant
+                    let addr = 0x23C0
+                        | (self.v & 0x0C00)
+                        | ((self.v >> 4) & 0x38)
+                        | ((self.v >> 2) & 0x07);
+                    let mut attrib = self.read(addr, 1)[0];
+                    if self.v & 0x0040 != 0 {
+                        attrib >>= 4;
                     }
-                    //unknown bytes fetch
-                    337..=340 => {}
-                    _ => {
-                        unreachable!("WE ARE ON A SCANLINE DOT THAT DOES NOT EXIST")
+                    if self.v & 0x0002 != 0 {
+                        attrib >>= 2;
                     }
+                    self.bg_next_attrib = attrib & 0x03;
                 }
+                4 => self.bg_next_lo = self.read(self.pattern_addr(), 1)[0],
+                6 => self.bg_next_hi = self.read(self.pattern_addr() + 8, 1)[0],
+                7 => self.increment_x(),
+                _ => {}
             }
+        }
 
-            //post-render scanline
-            240 => {}
-            //vblanking
-            241..=260 => {}
-            //dummy line
-            261 => {
-                //During pixels 280 through 304 of this scanline,
-                //the vertical scroll bits are reloaded if rendering is enabled.
+        match dot {
+            256 => self.increment_y(),
+            257 => {
+                self.load_background_shifters();
+                //v: ....A.. ...BCDEF <- t: ....A.. ...BCDEF
+                self.v = (self.v & !0x041F) | (self.t & 0x041F);
             }
-            _ => unreachable!("IN A SCANLINE THAT DOESNT EXIST"),
+            //two unused nametable fetches to round off the line
+            337 | 339 => {
+                self.bg_next_tile = self.read(0x2000 | (self.v & 0x0FFF), 1)[0];
+            }
+            _ => {}
         }
 
-        self.tick_beam();
-    }*/
+        //the pre-render line copies the vertical bits back so the next frame starts at the top
+        if self.cur_line == self.pre_render_line() && (280..=304).contains(&dot) {
+            //v: GHIA.BC DEF..... <- t: GHIA.BC DEF.....
+            self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+        }
+    }
 
-    pub fn step(&mut self) -> Result<String, String> {
-        let mut log_line = String::new();
-        match self.cur_line {
-            //visible lines
-            0..=239 => {
-                //For each pixel in the background buffer,
-                //the corresponding sprite pixel replaces it
-                //only if the sprite pixel is opaque and front priority
-                //or if the background pixel is transparent.
-
-                //what row of tiles are we on
-                let tile_row = self.cur_line / 8;
-
-                //TODO: how do we know what nametable to read from?
-                //since even with mirroring there are still 2 to choose from
-                let nametable_base = 0x2000;
-                let addr = nametable_base + tile_row * 32;
-
-                //fetch 32 tiles (32 bytes) these bytes are actually indexes into our pattern table
-                let tile_data = self.read(addr as u16, 32);
-
-                //now we need to get our patterns
-                for (i, tile) in tile_data.iter().enumerate() {
-                    let pattern_table_base = 0x0000;
-                    let pattern_addr = pattern_table_base + tile * 16;
-                    let pattern_raw = self.read(pattern_addr as u16, 16);
-                    let pattern_hi_plane = &pattern_raw[0..8];
-                    let pattern_lo_plane = &pattern_raw[8..16];
-
-                    //bitwise or every byte in the pattern planes to yield our final pattern
-                    let pattern_final: Vec<u8> = pattern_hi_plane
-                        .iter()
-                        .enumerate()
-                        .map(|(i, v)| *v | pattern_lo_plane[i])
-                        .collect();
-
-                    let pixels = pattern_final[self.cur_line % 8];
-                    let pixels_rgb = self.u8_to_rgb(pixels);
-                    let framebuffer_addr = self.cur_line * 256 * 3 + i * 8 * 3;
-                    for i in 0..24 {
-                        self.frame[framebuffer_addr + i] = pixels_rgb[i];
+    //pattern table address of the low plane for the current tile's row, fine y comes from v
+    fn pattern_addr(&self) -> u16 {
+        let base = if self.regs.PPUCTRL.background_tile_select {
+            0x1000
+        } else {
+            0x0000
+        };
+        base + self.bg_next_tile as u16 * 16 + ((self.v >> 12) & 0x07)
+    }
+
+    //the next tile goes into the low 8 bits, the attribute bits get stretched across all 8 pixels
+    fn load_background_shifters(&mut self) {
+        self.bg_shift_pattern_lo = (self.bg_shift_pattern_lo & 0xFF00) | self.bg_next_lo as u16;
+        self.bg_shift_pattern_hi = (self.bg_shift_pattern_hi & 0xFF00) | self.bg_next_hi as u16;
+        self.bg_shift_attrib_lo = (self.bg_shift_attrib_lo & 0xFF00)
+            | if self.bg_next_attrib & 0x01 != 0 {
+                0xFF
+            } else {
+                0x00
+            };
+        self.bg_shift_attrib_hi = (self.bg_shift_attrib_hi & 0xFF00)
+            | if self.bg_next_attrib & 0x02 != 0 {
+                0xFF
+            } else {
+                0x00
+            };
+    }
+
+    //coarse x lives in v bits 0-4. going off the right edge flips to the next horizontal nametable
+    fn increment_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    //fine y is bits 12-14, coarse y bits 5-9. row 29 is the last real row, so wrapping
+    //from there flips to the next vertical nametable. rows 30 and 31 (attribute memory)
+    //wrap to 0 without the flip
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+        } else {
+            self.v &= !0x7000;
+            let mut coarse_y = (self.v & 0x03E0) >> 5;
+            if coarse_y == 29 {
+                coarse_y = 0;
+                self.v ^= 0x0800;
+            } else if coarse_y == 31 {
+                coarse_y = 0;
+            } else {
+                coarse_y += 1;
+            }
+            self.v = (self.v & !0x03E0) | (coarse_y << 5);
+        }
+    }
+
+    /*sprite work for the NEXT line
+    dots 1-64: clear secondary OAM to $FF
+    dots 65-256: evaluation, which we do all at once at the end
+    dots 257-320: 8 sprite fetches, 8 dots each (2 garbage nametable reads, pattern lo, pattern hi)*/
+    fn sprite_dot(&mut self) {
+        let dot = self.cur_dot;
+        match dot {
+            1..=64 if dot.is_multiple_of(2) => {
+                self.secondary_oam[dot / 2 - 1] = 0xFF;
+            }
+            256 => self.evaluate_sprites(),
+            257..=320 => {
+                //OAMADDR gets zeroed all through the sprite fetches
+                self.regs.OAMADDR = 0;
+                let slot = (dot - 257) / 8;
+                if slot == 0 && dot == 257 {
+                    self.sprite_count = self.secondary_count;
+                    self.sprite_zero_line = self.sprite_zero_next;
+                }
+
+                match (dot - 257) % 8 {
+                    4 | 6 => {
+                        //empty slots still fetch tile $FF, mappers watching the bus can tell
+                        let entry = &self.secondary_oam[slot * 4..slot * 4 + 4];
+                        let (y, tile, attrib, x) = (entry[0], entry[1], entry[2], entry[3]);
+                        let row = (self.cur_line as u16).wrapping_sub(y as u16);
+                        let addr = self.sprite_pattern_addr(tile, attrib, row);
+
+                        if (dot - 257) % 8 == 4 {
+                            let mut lo = self.read(addr, 1)[0];
+                            if slot >= self.sprite_count {
+                                lo = 0;
+                            } else if attrib & 0x40 != 0 {
+                                lo = lo.reverse_bits();
+                            }
+                            self.sprite_lo[slot] = lo;
+                            self.sprite_attrib[slot] = attrib;
+                            self.sprite_x[slot] = x;
+                        } else {
+                            let mut hi = self.read(addr + 8, 1)[0];
+                            if slot >= self.sprite_count {
+                                hi = 0;
+                            } else if attrib & 0x40 != 0 {
+                                hi = hi.reverse_bits();
+                            }
+                            self.sprite_hi[slot] = hi;
+                        }
                     }
-                    write!(
-                        &mut log_line,
-                        "PPU: line is {}, RGB data is: {:?}",
-                        self.cur_line, pixels_rgb
-                    )
-                    .unwrap();
+                    _ => {}
                 }
             }
-            //post-render scanline
-            240 => {
-                //literally do nothing. safe to access ppu memory, but no vblank flag has been raised
+            _ => {}
+        }
+    }
+
+    //find the first 8 sprites that land on the next line and copy them into secondary OAM
+    fn evaluate_sprites(&mut self) {
+        self.secondary_count = 0;
+        self.sprite_zero_next = false;
+        //nothing gets drawn after line 239, and the pre-render line doesnt evaluate
+        if self.cur_line >= 240 {
+            return;
+        }
+
+        let height = self.sprite_height();
+        let mut n = 0;
+        while n < 64 && self.secondary_count < 8 {
+            let y = self.OAM[n * 4] as usize;
+            if self.cur_line >= y && self.cur_line < y + height {
+                let slot = self.secondary_count * 4;
+                self.secondary_oam[slot..slot + 4].copy_from_slice(&self.OAM[n * 4..n * 4 + 4]);
+                if n == 0 {
+                    self.sprite_zero_next = true;
+                }
+                self.secondary_count += 1;
             }
-            //vblanking
-            241..=260 => {}
-            //dummy line
-            261 => {
-                //During pixels 280 through 304 of this scanline,
-                //the vertical scroll bits are reloaded if rendering is enabled.
-
-                if self.cur_dot == 341 {
-                    //self.channel.send(vec![255; 184_320]).unwrap();
-                    self.channel.send(self.frame.clone()).unwrap();
-                    self.tick_beam();
-                    return Err("just sent a frame".to_string());
-                };
+            n += 1;
+        }
+
+        //once secondary OAM is full the ppu keeps scanning for a 9th sprite to set the overflow flag.
+        //except it bumps m (the byte within the sprite) along with n every time it misses, so it
+        //ends up reading tiles, attributes and x positions as y coordinates diagonally across OAM.
+        //that means both false positives and missed overflows, just like the real thing
+        let mut m = 0;
+        while n < 64 {
+            let y = self.OAM[n * 4 + m] as usize;
+            if self.cur_line >= y && self.cur_line < y + height {
+                self.regs.PPUSTATUS.SPRITE_OVERFLOW = true;
+                break;
             }
-            _ => unreachable!("IN A SCANLINE THAT DOESNT EXIST"),
+            n += 1;
+            m = (m + 1) & 0x03;
         }
+    }
 
-        self.tick_beam();
-        return Ok(log_line);
+    fn sprite_height(&self) -> usize {
+        if self.regs.PPUCTRL.sprite_height {
+            16
+        } else {
+            8
+        }
     }
 
-    fn tick_beam(&mut self) {
-        //increase the dot we're on, wrapping to 0 the end of line
-        if self.cur_dot >= 341 {
-            self.cur_dot = 0;
-            //if we're wrapping the dot, we need to increase our line,
-            //wrapping to 0 after all lines
-            if self.cur_line >= 261 {
-                self.cur_line = 0;
+    //low plane address for one row of a sprite. 8x16 sprites pick their pattern table
+    //with bit 0 of the tile number instead of PPUCTRL, and are two tiles stacked on top of each other
+    fn sprite_pattern_addr(&self, tile: u8, attrib: u8, row: u16) -> u16 {
+        let height = self.sprite_height() as u16;
+        let mut row = row % height;
+        if attrib & 0x80 != 0 {
+            row = height - 1 - row;
+        }
+
+        if height == 16 {
+            let table = (tile as u16 & 0x01) * 0x1000;
+            let mut tile = tile as u16 & 0xFE;
+            if row >= 8 {
+                tile += 1;
+                row -= 8;
+            }
+            table + tile * 16 + row
+        } else {
+            let table = if self.regs.PPUCTRL.sprite_tile_select {
+                0x1000
             } else {
-                self.cur_line += 1;
+                0x0000
+            };
+            table + tile as u16 * 16 + row
+        }
+    }
+
+    //the first opaque sprite pixel at this x, as (pixel, palette, behind background, is sprite 0)
+    fn sprite_pixel(&self, x: usize) -> (u8, u8, bool, bool) {
+        for i in 0..self.sprite_count {
+            let offset = x.wrapping_sub(self.sprite_x[i] as usize);
+            if offset < 8 {
+                let bit = 7 - offset;
+                let pixel =
+                    ((self.sprite_lo[i] >> bit) & 0x01) | ((self.sprite_hi[i] >> bit) & 0x01) << 1;
+                if pixel != 0 {
+                    let attrib = self.sprite_attrib[i];
+                    return (
+                        pixel,
+                        attrib & 0x03,
+                        attrib & 0x20 != 0,
+                        i == 0 && self.sprite_zero_line,
+                    );
+                }
             }
+        }
+        (0, 0, false, false)
+    }
+
+    //put the pixel for the current dot into the framebuffer
+    fn draw_pixel(&mut self) {
+        let x = self.cur_dot - 1;
+
+        let mut bg_pixel = 0;
+        let mut bg_palette = 0;
+        //LEFT_BACKGROUND_HIDE is actually a show bit, 0 blanks the leftmost 8 pixels
+        if self.regs.PPUMASK.BACKGROUND_EABLE && (x >= 8 || self.regs.PPUMASK.LEFT_BACKGROUND_HIDE)
+        {
+            //fine x picks which bit out of the top 8 we're looking at
+            let mux = 0x8000 >> self.x;
+            bg_pixel = ((self.bg_shift_pattern_lo & mux) != 0) as u8
+                | (((self.bg_shift_pattern_hi & mux) != 0) as u8) << 1;
+            bg_palette = ((self.bg_shift_attrib_lo & mux) != 0) as u8
+                | (((self.bg_shift_attrib_hi & mux) != 0) as u8) << 1;
+        }
+
+        //same deal for sprites with LEFT_SPRITE_HIDE
+        let (sp_pixel, sp_palette, sp_behind, sp_zero) =
+            if self.regs.PPUMASK.SPRITE_ENABLE && (x >= 8 || self.regs.PPUMASK.LEFT_SPRITE_HIDE) {
+                self.sprite_pixel(x)
+            } else {
+                (0, 0, false, false)
+            };
+
+        //sprite 0 hit: opaque sprite 0 over opaque background, priority doesnt matter.
+        //the clipped left column already comes out transparent above, and x=255 never hits
+        if sp_zero && sp_pixel != 0 && bg_pixel != 0 && x != 255 {
+            self.regs.PPUSTATUS.SPRITE_0_HIT = true;
+        }
+
+        let entry = if !self.rendering() && (self.v & 0x3F00) == 0x3F00 {
+            //with rendering off, pointing v into palette ram shows that color instead of the backdrop
+            (self.v & 0x1F) as u8
         } else {
-            self.cur_dot += 1
+            match (bg_pixel, sp_pixel) {
+                (0, 0) => 0,
+                (0, _) => 0x10 | sp_palette << 2 | sp_pixel,
+                (_, 0) => bg_palette << 2 | bg_pixel,
+                //both opaque, the sprite's priority bit decides
+                _ if sp_behind => bg_palette << 2 | bg_pixel,
+                _ => 0x10 | sp_palette << 2 | sp_pixel,
+            }
+        };
+
+        let rgb = self.palette_rgb(entry);
+        let framebuffer_addr = (self.cur_line * 256 + x) * 3;
+        self.frame[framebuffer_addr..framebuffer_addr + 3].copy_from_slice(&rgb);
+    }
+
+    //power up state (https://www.nesdev.org/wiki/PPU_power_up_state)
+    pub fn power_on(&mut self) {
+        self.regs = PPUREGS::new();
+        self.v = 0;
+        self.t = 0;
+        self.x = 0;
+        self.w = false;
+        self.read_buffer = 0;
+        self.io_latch = 0;
+        self.nmi_previous = false;
+        self.nmi_pending = false;
+        self.suppress_vblank = false;
+        self.warming_up = true;
+        self.cur_line = 0;
+        self.cur_dot = 0;
+    }
+
+    //reset only clears some of it. v, OAM, palette and vram all survive
+    pub fn reset(&mut self) {
+        self.regs.PPUCTRL = 0.into();
+        self.regs.PPUMASK = 0.into();
+        self.t = 0;
+        self.x = 0;
+        self.w = false;
+        self.read_buffer = 0;
+        self.nmi_previous = false;
+        self.nmi_pending = false;
+        self.suppress_vblank = false;
+        self.warming_up = true;
+    }
+
+    //cpu side of the ppu registers, reg is 0-7 ($2000-$2007)
+    pub fn reg_read(&mut self, reg: u16) -> u8 {
+        let val = match reg {
+            0x2 => {
+                //reading status clears vblank and resets the write toggle
+                let val = self.reg_peek(reg);
+                self.regs.PPUSTATUS.VBLANK = false;
+                self.w = false;
+                self.update_nmi();
+
+                //racing the start of vblank. cur_dot is the dot we're about to run, so
+                //1 means the flag goes up on the very next ppu clock: we read it clear and it
+                //stays clear. reading on or just after the dot it goes up sees it set, but
+                //the nmi for this frame never happens
+                if self.cur_line == 241 {
+                    match self.cur_dot {
+                        1 => self.suppress_vblank = true,
+                        2 | 3 => self.nmi_pending = false,
+                        _ => {}
+                    }
+                }
+                val
+            }
+            0x4 => self.reg_peek(reg),
+            0x7 => {
+                let addr = self.v & 0x3FFF;
+                let val = if addr >= 0x3F00 {
+                    //palette reads skip the buffer, but the buffer still gets filled
+                    //with the nametable byte "underneath" the palette
+                    self.read_buffer = self.read(addr - 0x1000, 1)[0];
+                    self.palette_read(addr) | (self.io_latch & 0xC0)
+                } else {
+                    let val = self.read_buffer;
+                    self.read_buffer = self.read(addr, 1)[0];
+                    val
+                };
+                self.increment_v();
+                val
+            }
+            //everything else is write only
+            _ => self.io_latch,
         };
+        self.io_latch = val;
+        val
+    }
+
+    //what a register read would return, without any of the side effects. for debug/logging
+    pub fn reg_peek(&self, reg: u16) -> u8 {
+        match reg {
+            0x2 => {
+                let status: u8 = self.regs.PPUSTATUS.into();
+                (status & 0xE0) | (self.io_latch & 0x1F)
+            }
+            0x4 => {
+                //bits 2-4 of the attribute byte dont exist, they always read back 0
+                let val = self.OAM[self.regs.OAMADDR as usize];
+                if self.regs.OAMADDR % 4 == 2 {
+                    val & 0xE3
+                } else {
+                    val
+                }
+            }
+            0x7 => {
+                let addr = self.v & 0x3FFF;
+                if addr >= 0x3F00 {
+                    self.palette_read(addr) | (self.io_latch & 0xC0)
+                } else {
+                    self.read_buffer
+                }
+            }
+            _ => self.io_latch,
+        }
     }
 
-    fn u8_to_rgb(&self, pixels: u8) -> Vec<u8> {
-        let mut ret_vec = vec![0; 24];
-        for i in 7..=0 {
-            let bit = (pixels >> i & 0x1) != 0;
-            if bit {
-                ret_vec[(7 - i) * 3] = 255;
-                ret_vec[((7 - i) * 3) + 1] = 255;
-                ret_vec[((7 - i) * 3) + 2] = 255;
+    pub fn reg_write(&mut self, reg: u16, byte: u8) {
+        self.io_latch = byte;
+        if self.warming_up && matches!(reg, 0x0 | 0x1 | 0x5 | 0x6) {
+            return;
+        }
+        match reg {
+            0x0 => {
+                self.regs.PPUCTRL = byte.into();
+                //turning nmi_enable on in the middle of vblank fires an nmi right away
+                self.update_nmi();
+                //t: ...GH.. ........ <- d: ......GH
+                self.t = (self.t & !0x0C00) | ((byte as u16 & 0x03) << 10);
+            }
+            0x1 => self.regs.PPUMASK = byte.into(),
+            //status is read only
+            0x2 => {}
+            0x3 => self.regs.OAMADDR = byte,
+            0x4 => {
+                if self.rendering() && self.render_line() {
+                    //OAM is busy with sprite evaluation. the write gets dropped and
+                    //OAMADDR gets a glitchy bump of its top 6 bits instead
+                    self.regs.OAMADDR = self.regs.OAMADDR.wrapping_add(4);
+                } else {
+                    self.OAM[self.regs.OAMADDR as usize] = byte;
+                    self.regs.OAMADDR = self.regs.OAMADDR.wrapping_add(1);
+                }
             }
+            0x5 => {
+                if !self.w {
+                    //t: ....... ...ABCDE <- d: ABCDE...
+                    //x:              FGH <- d: .....FGH
+                    self.t = (self.t & !0x001F) | (byte as u16 >> 3);
+                    self.x = byte & 0x07;
+                } else {
+                    //t: FGH..AB CDE..... <- d: ABCDEFGH
+                    self.t = (self.t & !0x73E0)
+                        | ((byte as u16 & 0x07) << 12)
+                        | ((byte as u16 >> 3) << 5);
+                }
+                self.w = !self.w;
+            }
+            0x6 => {
+                if !self.w {
+                    //t: .CDEFGH ........ <- d: ..CDEFGH
+                    //bit 14 of t gets cleared too
+                    self.t = (self.t & 0x00FF) | ((byte as u16 & 0x3F) << 8);
+                } else {
+                    //t: ....... ABCDEFGH <- d: ABCDEFGH, then v = t
+                    self.t = (self.t & 0xFF00) | byte as u16;
+                    self.v = self.t;
+                    //v goes straight out on the address bus, which is how games poke mmc3's irq
+                    self.cart.ppu_bus(self.v & 0x3FFF, self.cycles);
+                }
+                self.w = !self.w;
+            }
+            0x7 => {
+                self.write(self.v & 0x3FFF, &[byte]);
+                self.increment_v();
+            }
+            _ => unreachable!("TRIED TO WRITE A PPU CONTROL REG THAT DOESNT EXIST"),
         }
+    }
 
-        return ret_vec;
+    //latch a new nmi whenever the ppu's nmi output goes from off to on
+    fn update_nmi(&mut self) {
+        let nmi = self.regs.PPUSTATUS.VBLANK && self.regs.PPUCTRL.nmi_enable;
+        if nmi && !self.nmi_previous {
+            self.nmi_pending = true;
+        }
+        self.nmi_previous = nmi;
+    }
+
+    //PPUDATA accesses bump v by 1 (across) or 32 (down) depending on PPUCTRL.
+    //while rendering, the ppu is already using v, so it does a coarse x and a y increment instead
+    fn increment_v(&mut self) {
+        if self.rendering() && self.render_line() {
+            self.increment_x();
+            self.increment_y();
+            return;
+        }
+
+        let step = if self.regs.PPUCTRL.increment_mode {
+            32
+        } else {
+            1
+        };
+        self.v = self.v.wrapping_add(step) & 0x7FFF;
+    }
+
+    //$3F10/$3F14/$3F18/$3F1C arent real, they're the backdrop entries of the background palettes
+    fn palette_index(addr: u16) -> usize {
+        let idx = (addr & 0x1F) as usize;
+        if idx >= 0x10 && idx.is_multiple_of(4) {
+            idx - 0x10
+        } else {
+            idx
+        }
+    }
+
+    //palette ram is only 6 bits wide. greyscale mode masks off the hue, reads included
+    pub fn palette_read(&self, addr: u16) -> u8 {
+        let val = self.palette[Ppu::palette_index(addr)] & 0x3F;
+        if self.regs.PPUMASK.GREYSCALE {
+            val & 0x30
+        } else {
+            val
+        }
+    }
+    pub fn palette_write(&mut self, addr: u16, byte: u8) {
+        self.palette[Ppu::palette_index(addr)] = byte & 0x3F;
+    }
+
+    //PPUMASK emphasis bits as BGR. the pal ppu has red and green swapped
+    fn emphasis(&self) -> u8 {
+        let mask = self.regs.PPUMASK;
+        let (red, green) = if self.pal {
+            (mask.GREEN, mask.RED)
+        } else {
+            (mask.RED, mask.GREEN)
+        };
+        red as u8 | (green as u8) << 1 | (mask.BLUE as u8) << 2
+    }
+
+    //palette entry (0-31) -> the rgb color it currently represents
+    pub fn palette_rgb(&self, entry: u8) -> [u8; 3] {
+        self.master_palette
+            .rgb(self.palette_read(0x3F00 + entry as u16), self.emphasis())
+    }
+
+    fn tick_beam(&mut self) {
+        self.cycles += 1;
+        self.cur_dot += 1;
+        let pre_render = self.pre_render_line();
+        //odd frames skip the last dot of the pre-render line when rendering is on (ntsc only,
+        //pal frames are always the same length)
+        let line_length = if !self.pal
+            && self.cur_line == pre_render
+            && !self.frames.is_multiple_of(2)
+            && self.rendering()
+        {
+            340
+        } else {
+            341
+        };
+        //wrap to 0 at the end of line, and the line to 0 after all lines
+        if self.cur_dot >= line_length {
+            self.cur_dot = 0;
+            if self.cur_line >= pre_render {
+                self.cur_line = 0;
+            } else {
+                self.cur_line += 1;
+            }
+        }
     }
 }
@@ -0,0 +1,75 @@
+use crate::apu::{AudioOutput, CHANNEL_NAMES};
+use crate::nes::NES;
+use std::fs;
+use std::path::Path;
+
+//runs the system with no window and no debugger for a set number of frames, then writes everything
+//the apu played to a wav file. with per_channel, every channel also gets its own file next to it
+//(out.wav -> out.pulse1.wav, out.triangle.wav...), mixed on its own through the same tables and filters
+pub fn dump(
+    nes: &mut NES,
+    path: &str,
+    frames: usize,
+    sample_rate: u32,
+    per_channel: bool,
+) -> Result<(), String> {
+    nes.apu.output = Some(AudioOutput::new(nes.pal, sample_rate, None));
+    if per_channel {
+        nes.apu.channel_outputs = Some(
+            CHANNEL_NAMES
+                .iter()
+                .map(|_| AudioOutput::new(nes.pal, sample_rate, None))
+                .collect(),
+        );
+    }
+
+    let target = nes.ppu.frames + frames;
+    while nes.ppu.frames < target {
+        nes.step()?;
+        //nobody is watching, frame messages dont mean anything here
+        nes.ppu_messages.clear();
+    }
+
+    if let Some(output) = &mut nes.apu.output {
+        write(Path::new(path), sample_rate, &output.finish())?;
+    }
+    if let Some(outputs) = &mut nes.apu.channel_outputs {
+        for (name, output) in CHANNEL_NAMES.iter().zip(outputs.iter_mut()) {
+            let channel_path = Path::new(path).with_extension(format!("{name}.wav"));
+            write(&channel_path, sample_rate, &output.finish())?;
+        }
+    }
+    Ok(())
+}
+
+/*16 bit mono pcm wav
+0   "RIFF", file size - 8
+8   "WAVE"
+12  "fmt ", 16 byte chunk: format (1 = pcm), channels, sample rate, byte rate, block align, bits
+36  "data", data size, then the samples*/
+pub fn write(path: &Path, sample_rate: u32, samples: &[f32]) -> Result<(), String> {
+    let data_size = samples.len() as u32 * 2;
+    let mut bytes: Vec<u8> = Vec::with_capacity(44 + data_size as usize);
+
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&pcm.to_le_bytes());
+    }
+
+    fs::write(path, bytes).map_err(|e| format!("couldnt write {}: {e}", path.display()))
+}